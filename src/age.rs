@@ -0,0 +1,57 @@
+use std::time::{Duration, SystemTime};
+use crate::dirfd::Stat;
+
+/// one of the times a file has, for `--age-by`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Timestamp {
+    Accessed,
+    Modified,
+    Changed,
+}
+
+impl Timestamp {
+    fn of(self, stat: &Stat) -> SystemTime {
+        match self {
+            Timestamp::Accessed => stat.accessed(),
+            Timestamp::Modified => stat.modified(),
+            Timestamp::Changed => stat.changed(),
+        }
+    }
+}
+
+/// only things older than this get deleted, for `--tmpfiles` and `--not-accessed-since`
+pub struct Age {
+    cutoff: SystemTime,
+    by: Vec<Timestamp>,
+}
+
+impl Age {
+    /// everything last touched more than `age` before `now`, going by the newest of `by`
+    pub fn new(age: Duration, now: SystemTime, by: Vec<Timestamp>) -> Age {
+        Age { cutoff: now.checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH), by }
+    }
+
+    /// parses a comma separated list of `atime`, `mtime` and `ctime`
+    pub fn parse_by(by: &str) -> Option<Vec<Timestamp>> {
+        by.split(',')
+            .map(|t| match t.trim() {
+                "atime" => Some(Timestamp::Accessed),
+                "mtime" => Some(Timestamp::Modified),
+                "ctime" => Some(Timestamp::Changed),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// returns true if every time we go by is before the cutoff. like systemd-tmpfiles, a file
+    /// that was used recently in any of the ways we look at is still in use
+    pub fn is_old(&self, stat: &Stat) -> bool {
+        self.by.iter().all(|t| t.of(stat) < self.cutoff)
+    }
+
+    /// `is_old` for a directory, from its stat before we went into it. listing a directory
+    /// updates its atime, so going by that would have every run keep them around for the next
+    pub fn is_old_dir(&self, stat: &Stat) -> bool {
+        self.by.iter().filter(|t| **t != Timestamp::Accessed).all(|t| t.of(stat) < self.cutoff)
+    }
+}
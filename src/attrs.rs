@@ -0,0 +1,52 @@
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::Path;
+use crate::dirfd::Dir;
+
+// from linux/fs.h, libc doesn't have these
+const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+const FS_APPEND_FL: libc::c_int = 0x20;
+
+/// clears the immutable and append-only attributes of `fd` if it has them, saying so. the
+/// kernel takes an int for these, whatever the ioctl number says
+fn clear_fd(fd: RawFd, path: &Path) -> io::Result<bool> {
+    let mut flags: libc::c_int = 0;
+    if unsafe { libc::ioctl(fd, libc::FS_IOC_GETFLAGS, &mut flags) } < 0 {
+        let e = io::Error::last_os_error();
+        // not every filesystem has attributes
+        return if e.raw_os_error() == Some(libc::ENOTTY) { Ok(false) } else { Err(e) };
+    }
+    let set = flags & (FS_IMMUTABLE_FL | FS_APPEND_FL);
+    if set == 0 {
+        return Ok(false);
+    }
+    let cleared = flags & !set;
+    if unsafe { libc::ioctl(fd, libc::FS_IOC_SETFLAGS, &cleared) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if set & FS_IMMUTABLE_FL != 0 {
+        println!("cleared the immutable attribute of {}", path.display());
+    }
+    if set & FS_APPEND_FL != 0 {
+        println!("cleared the append-only attribute of {}", path.display());
+    }
+    Ok(true)
+}
+
+/// clears what keeps `name` from being removed from `dir`, for `--clear-attrs`. that's the
+/// immutable or append-only attribute on it, or either on the directory. returns true if
+/// anything was cleared, so removing it is worth another try
+pub fn clear(dir: &Dir, name: &OsStr, path: &Path) -> io::Result<bool> {
+    let mut cleared = false;
+    match dir.open_child_file(name) {
+        Ok(file) => cleared |= clear_fd(file.as_raw_fd(), path)?,
+        // symlinks don't have attributes
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => {}
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = path.parent() {
+        cleared |= clear_fd(dir.as_raw_fd(), parent)?;
+    }
+    Ok(cleared)
+}
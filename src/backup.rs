@@ -0,0 +1,69 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::dirfd::{Dir, Stat};
+
+/// where `path` goes under `dir`, keeping its path relative to the parent of where we started
+pub(crate) fn keep_structure(dir: &Path, path: &Path, starting_dir: &Path) -> PathBuf {
+    let base = starting_dir.parent().unwrap_or(Path::new("/"));
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    let relative = relative.strip_prefix("/").unwrap_or(relative);
+    dir.join(relative)
+}
+
+/// returns `destination` if nothing is there yet, otherwise the first free `destination.N`
+pub(crate) fn unclaimed(destination: PathBuf) -> PathBuf {
+    if destination.symlink_metadata().is_err() {
+        return destination;
+    }
+    let mut n = 2;
+    loop {
+        let mut name = destination.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", n));
+        let candidate = destination.with_file_name(name);
+        if candidate.symlink_metadata().is_err() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// a directory that files are copied into before they're deleted
+pub struct Backup {
+    pub dir: PathBuf,
+    /// hardlink instead of copying when the file is on the same device as `dir`
+    pub hardlink: bool,
+    /// how to copy when not hardlinking
+    pub reflink: crate::reflink::Reflink,
+    dev: u64,
+}
+
+impl Backup {
+    /// opens (and creates if needed) a backup directory
+    pub fn open(dir: PathBuf, hardlink: bool) -> io::Result<Backup> {
+        std::fs::create_dir_all(&dir)?;
+        let dev = Dir::open(&dir)?.stat()?.dev();
+        Ok(Backup { dir, hardlink, reflink: crate::reflink::Reflink::Never, dev })
+    }
+
+    /// where a file will be backed up to, keeping its path relative to the parent of where we started
+    fn destination(&self, path: &Path, starting_dir: &Path) -> PathBuf {
+        // don't clobber an earlier backup of the same path
+        unclaimed(keep_structure(&self.dir, path, starting_dir))
+    }
+
+    /// backs up the file `name` in `dir` (which lives at `path`) before it gets deleted
+    pub fn backup(&self, dir: &Dir, name: &OsStr, path: &Path, stat: &Stat, starting_dir: &Path) -> io::Result<()> {
+        let destination = self.destination(path, starting_dir);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // a hardlink keeps the data around for free, but only works on the same device
+        if self.hardlink && stat.dev() == self.dev {
+            log::debug!("hardlinking {} to {}", path.display(), destination.display());
+            return dir.link_out(name, &destination);
+        }
+        log::debug!("copying {} to {}", path.display(), destination.display());
+        crate::trash::copy_file(path, stat, &destination, self.reflink)
+    }
+}
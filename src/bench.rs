@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::{delete, format_size, DeleteOptions, RunState};
+
+/// what the synthetic tree for `--bench` looks like
+pub struct Shape {
+    /// how many files and how many subdirectories each directory gets
+    pub width: usize,
+    /// how many levels of subdirectories there are below the top
+    pub depth: usize,
+    pub file_size: u64,
+}
+
+/// fills `dir` with `shape`, returning how many files and directories it made
+fn build(dir: &Path, shape: &Shape, depth: usize) -> io::Result<(usize, usize)> {
+    let contents = vec![0u8; shape.file_size as usize];
+    let (mut files, mut dirs) = (0, 0);
+    for i in 0..shape.width {
+        std::fs::File::create(dir.join(format!("file{}", i)))?.write_all(&contents)?;
+        files += 1;
+    }
+    if depth < shape.depth {
+        for i in 0..shape.width {
+            let child = dir.join(format!("dir{}", i));
+            std::fs::create_dir(&child)?;
+            let (child_files, child_dirs) = build(&child, shape, depth + 1)?;
+            files += child_files;
+            dirs += child_dirs + 1;
+        }
+    }
+    Ok((files, dirs))
+}
+
+/// makes a synthetic tree in a new directory under `parent`, times deleting it with `options`
+/// and reports how fast it went. nothing outside the new directory is touched, and whatever
+/// the run leaves of it is cleaned up afterwards. with `--io-uring` it's a comparison instead
+pub fn bench(parent: &Path, shape: &Shape, options: &mut DeleteOptions) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if options.io_uring.is_some() {
+        return compare(parent, shape, options);
+    }
+    bench_once(parent, shape, options).map(|_| ())
+}
+
+/// times the serial path, the threaded one from `--parallel-stat` and the ring against each
+/// other, making the tree again for each, and says how much faster the ring was
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+fn compare(parent: &Path, shape: &Shape, options: &mut DeleteOptions) -> io::Result<()> {
+    let ring = options.io_uring.take();
+    let parallel_stat = std::mem::replace(&mut options.parallel_stat, false);
+    println!("serially:");
+    let serial = bench_once(parent, shape, options)?;
+    options.parallel_stat = true;
+    println!("with --parallel-stat:");
+    let threaded = bench_once(parent, shape, options)?;
+    options.parallel_stat = parallel_stat;
+    options.io_uring = ring;
+    println!("with --io-uring:");
+    let uring = bench_once(parent, shape, options)?;
+    // nothing to compare if a run didn't delete anything, which is usually a missing -r
+    if serial > 0.0 && threaded > 0.0 {
+        println!("--io-uring went {:.2}x as fast as serially and {:.2}x as fast as with --parallel-stat", uring / serial, uring / threaded);
+    }
+    Ok(())
+}
+
+/// one timed run on a fresh tree, returning how many files a second it deleted
+fn bench_once(parent: &Path, shape: &Shape, options: &mut DeleteOptions) -> io::Result<f64> {
+    let root = parent.join(format!("saferm-bench-{}", std::process::id()));
+    std::fs::create_dir(&root)?;
+    println!("building a tree {} wide and {} deep in {}", shape.width, shape.depth, root.display());
+    let built = build(&root, shape, 0);
+    let mut per_sec = 0.0;
+    if let Ok((files, dirs)) = built {
+        let mut state = RunState::default();
+        println!("made {} files of {} in {} directories, deleting them", files, format_size(shape.file_size), dirs + 1);
+        let start = std::mem::replace(&mut options.starting_dir, root.clone());
+        let started = Instant::now();
+        delete(&root.to_string_lossy(), options, &mut state);
+        let elapsed = started.elapsed();
+        options.starting_dir = start;
+        // the fast path doesn't count as it goes, so go by what's left instead
+        let left = crate::leftovers(&root).iter().filter(|p| p.symlink_metadata().is_ok_and(|m| !m.is_dir())).count();
+        let deleted = files - left;
+        per_sec = deleted as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        println!("deleted {} of {} files in {:.3}s, {:.0} files/sec", deleted, files, elapsed.as_secs_f64(), per_sec);
+    }
+    cleanup(&root)?;
+    built.map(|_| per_sec)
+}
+
+/// removes what's left of the tree, without following anything out of it
+fn cleanup(root: &PathBuf) -> io::Result<()> {
+    match std::fs::symlink_metadata(root) {
+        Ok(m) if m.is_dir() => std::fs::remove_dir_all(root),
+        Ok(_) => std::fs::remove_file(root),
+        Err(_) => Ok(()),
+    }
+}
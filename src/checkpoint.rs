@@ -0,0 +1,98 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// how many entries to go through between writing the checkpoint out
+const WRITE_EVERY: usize = 1000;
+
+/// a record of which entries directly under the starting directory have been completely
+/// dealt with, so a run that got interrupted can pick up where it left off.
+///
+/// the file is the starting directory on the first line and then one finished name per line
+pub struct Checkpoint {
+    pub path: PathBuf,
+    starting_dir: PathBuf,
+    done: RefCell<HashSet<OsString>>,
+    /// entries gone through since the file was last written
+    since_write: Cell<usize>,
+    /// whether anything was finished since the file was last written
+    dirty: Cell<bool>,
+}
+
+impl Checkpoint {
+    /// reads the checkpoint file if there is one, it has to be for the same starting directory
+    pub fn open(path: PathBuf, starting_dir: &Path) -> io::Result<Checkpoint> {
+        let mut done = HashSet::new();
+        match std::fs::read(&path) {
+            Ok(contents) => {
+                let mut lines = contents.split(|&b| b == b'\n');
+                let start = lines.next().unwrap_or_default();
+                if start != starting_dir.as_os_str().as_bytes() {
+                    return Err(io::Error::other(format!(
+                        "{} is a checkpoint for {}, not {}",
+                        path.display(), String::from_utf8_lossy(start), starting_dir.display(),
+                    )));
+                }
+                done.extend(lines.filter(|l| !l.is_empty()).map(|l| OsString::from_vec(l.to_vec())));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Checkpoint {
+            path,
+            starting_dir: starting_dir.to_path_buf(),
+            done: RefCell::new(done),
+            since_write: Cell::new(0),
+            dirty: Cell::new(false),
+        })
+    }
+
+    /// returns true if an entry directly under the starting directory was finished by an earlier run
+    pub fn is_done(&self, name: &OsStr) -> bool {
+        self.done.borrow().contains(name)
+    }
+
+    /// counts an entry as gone through, writing the file out every so often
+    pub fn tick(&self) {
+        self.since_write.set(self.since_write.get() + 1);
+        if self.since_write.get() >= WRITE_EVERY && self.dirty.get() {
+            self.save_or_warn();
+        }
+    }
+
+    /// marks an entry directly under the starting directory as completely dealt with
+    pub fn finish(&self, name: &OsStr) {
+        // a name with a newline in it can't go in the file, so it'll just be done again
+        if !name.as_bytes().contains(&b'\n') {
+            self.done.borrow_mut().insert(name.to_os_string());
+            self.dirty.set(true);
+        }
+    }
+
+    /// writes the checkpoint to a temporary file and renames it over the old one, so an
+    /// interruption never leaves half a checkpoint behind
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = self.starting_dir.as_os_str().as_bytes().to_vec();
+        for name in self.done.borrow().iter() {
+            contents.push(b'\n');
+            contents.extend_from_slice(name.as_bytes());
+        }
+        contents.push(b'\n');
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, contents)?;
+        std::fs::rename(&temporary, &self.path)?;
+        self.since_write.set(0);
+        self.dirty.set(false);
+        Ok(())
+    }
+
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            println!("warning: couldn't write the checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+}
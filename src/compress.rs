@@ -0,0 +1,74 @@
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::prelude::*;
+
+/// how much gets read or written in one go
+const CHUNK: usize = 64 * 1024;
+
+#[link(name = "z")]
+extern "C" {
+    fn gzdopen(fd: libc::c_int, mode: *const libc::c_char) -> *mut libc::c_void;
+    fn gzwrite(file: *mut libc::c_void, buf: *const libc::c_void, len: libc::c_uint) -> libc::c_int;
+    fn gzread(file: *mut libc::c_void, buf: *mut libc::c_void, len: libc::c_uint) -> libc::c_int;
+    fn gzerror(file: *mut libc::c_void, errnum: *mut libc::c_int) -> *const libc::c_char;
+    fn gzclose(file: *mut libc::c_void) -> libc::c_int;
+}
+
+/// what zlib says went wrong with `file`. it sets errno for anything the system did, like
+/// running out of space, and has its own messages for the rest
+fn error(file: *mut libc::c_void) -> io::Error {
+    let mut errnum = 0;
+    let message = unsafe { gzerror(file, &mut errnum) };
+    // Z_ERRNO
+    if errnum == -1 || message.is_null() {
+        return io::Error::last_os_error();
+    }
+    io::Error::other(unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned())
+}
+
+/// opens `file` with zlib, which takes it over and closes it in `gzclose`
+fn open(file: File, mode: &CStr) -> io::Result<*mut libc::c_void> {
+    let gz = unsafe { gzdopen(file.into_raw_fd(), mode.as_ptr()) };
+    if gz.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(gz)
+}
+
+/// writes all of `from` to `to` gzipped
+pub fn compress(mut from: File, to: File) -> io::Result<()> {
+    let gz = open(to, c"wb")?;
+    let mut buf = vec![0; CHUNK];
+    let written = (|| loop {
+        match from.read(&mut buf)? {
+            0 => return Ok(()),
+            n => if unsafe { gzwrite(gz, buf.as_ptr().cast(), n as libc::c_uint) } == 0 {
+                return Err(error(gz));
+            },
+        }
+    })();
+    // the last of it is only written out when it's closed, so that can run out of space too
+    let closed = unsafe { gzclose(gz) };
+    written?;
+    if closed != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// writes the gzipped `from` to `to` as it was. a copy that was cut short is an error
+pub fn decompress(from: File, mut to: File) -> io::Result<()> {
+    let gz = open(from, c"rb")?;
+    let mut buf = vec![0; CHUNK];
+    let read = (|| loop {
+        match unsafe { gzread(gz, buf.as_mut_ptr().cast(), CHUNK as libc::c_uint) } {
+            0 => return Ok(()),
+            n if n < 0 => return Err(error(gz)),
+            n => to.write_all(&buf[..n as usize])?,
+        }
+    })();
+    unsafe { gzclose(gz) };
+    read?;
+    to.sync_all()
+}
@@ -0,0 +1,59 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use crate::dirfd::Stat;
+
+/// a copy that didn't work out, the original was left where it was
+pub struct Failed {
+    pub path: PathBuf,
+    pub stat: Stat,
+    pub error: io::Error,
+}
+
+#[derive(Default)]
+struct Shared {
+    running: usize,
+    failed: Vec<Failed>,
+}
+
+/// runs slow cross-device copies on their own threads so the walk can carry on, for
+/// `--trash-move-across-devices-async`
+pub struct Copier {
+    /// the most copies running at once
+    jobs: usize,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+}
+
+impl Copier {
+    pub fn new(jobs: usize) -> Copier {
+        Copier { jobs: jobs.max(1), shared: Arc::default() }
+    }
+
+    /// runs `copy` for the file at `path` in the background, first waiting for a free slot if
+    /// there are already as many copies going as we're allowed
+    pub fn start(&self, path: PathBuf, stat: Stat, copy: impl FnOnce() -> io::Result<()> + Send + 'static) {
+        let (lock, changed) = &*self.shared;
+        let mut shared = changed.wait_while(lock.lock().unwrap(), |s| s.running >= self.jobs).unwrap();
+        shared.running += 1;
+        drop(shared);
+        let handle = Arc::clone(&self.shared);
+        std::thread::spawn(move || {
+            let result = copy();
+            let (lock, changed) = &*handle;
+            let mut shared = lock.lock().unwrap();
+            shared.running -= 1;
+            if let Err(error) = result {
+                shared.failed.push(Failed { path, stat, error });
+            }
+            changed.notify_all();
+        });
+    }
+
+    /// waits for every copy that's running to finish, returning the ones that failed since
+    /// the last time we waited
+    pub fn wait(&self) -> Vec<Failed> {
+        let (lock, changed) = &*self.shared;
+        let mut shared = changed.wait_while(lock.lock().unwrap(), |s| s.running > 0).unwrap();
+        std::mem::take(&mut shared.failed)
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::space::{delete_candidate, Candidate};
+use crate::{format_size, DeleteOptions, RunState, SkipReason};
+
+/// which file of a group of duplicates is kept
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Keep {
+    Oldest,
+    Newest,
+}
+
+impl Keep {
+    pub fn parse(keep: &str) -> Option<Keep> {
+        match keep {
+            "oldest" => Some(Keep::Oldest),
+            "newest" => Some(Keep::Newest),
+            _ => None,
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    hash(std::fs::File::open(path)?)
+}
+
+/// the sha256 of everything in `file`
+pub(crate) fn hash(mut file: impl std::io::Read) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// returns true if a candidate has been written to since it was looked at, going by its size
+/// and modification time, so its hash can't be trusted anymore
+fn changed_since_hashed(candidate: &Candidate) -> io::Result<bool> {
+    let now = std::fs::symlink_metadata(&candidate.path)?;
+    Ok(now.len() != candidate.stat.size() || now.modified()? != candidate.stat.modified())
+}
+
+/// groups the held back candidates by content, using their size first so only files that
+/// could be the same get hashed. empty files and extra hardlinks to the same file don't count,
+/// deleting them wouldn't free anything
+fn duplicate_groups(candidates: Vec<Candidate>, state: &mut RunState) -> Vec<Vec<Candidate>> {
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    for candidate in candidates {
+        if candidate.stat.is_file() && candidate.stat.size() > 0 {
+            by_size.entry(candidate.stat.size()).or_default().push(candidate);
+        }
+    }
+    let mut groups = Vec::new();
+    for (_, mut same_size) in by_size {
+        same_size.sort_by_key(|c| (c.stat.dev(), c.stat.ino()));
+        same_size.dedup_by_key(|c| (c.stat.dev(), c.stat.ino()));
+        if same_size.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<[u8; 32], Vec<Candidate>> = HashMap::new();
+        for candidate in same_size {
+            match hash_file(&candidate.path) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(candidate),
+                Err(e) => {
+                    println!("error reading {}: {}", candidate.path.display(), e);
+                    state.record_failed(&candidate.path, &e);
+                }
+            }
+        }
+        groups.extend(by_hash.into_values().filter(|g| g.len() > 1));
+    }
+    groups
+}
+
+/// deletes all but one file of every group of held back candidates with the same content
+pub fn delete_duplicates(keep: Keep, options: &DeleteOptions, state: &mut RunState) {
+    let candidates = std::mem::take(&mut state.candidates);
+    let mut freed = 0;
+    let mut deleted = 0;
+    for mut group in duplicate_groups(candidates, state) {
+        group.sort_by_key(|c| c.stat.modified());
+        if keep == Keep::Newest {
+            group.reverse();
+        }
+        let (kept, duplicates) = group.split_first().unwrap();
+        for duplicate in duplicates {
+            // if either one changed after it was hashed, they might not be the same anymore
+            match changed_since_hashed(duplicate).and_then(|changed| Ok(changed || changed_since_hashed(kept)?)) {
+                Ok(false) => {}
+                Ok(true) => {
+                    state.skip_entry(&duplicate.path, &duplicate.stat, SkipReason::Changed, options);
+                    continue;
+                }
+                Err(e) => {
+                    println!("error checking {} is still a duplicate: {}", duplicate.path.display(), e);
+                    state.record_failed(&duplicate.path, &e);
+                    continue;
+                }
+            }
+            println!("{} is a duplicate of {}, deleting", duplicate.path.display(), kept.path.display());
+            if options.dryrun {
+                println!("(dryrun) did nothing");
+            } else if let Err(e) = delete_candidate(duplicate, options) {
+                println!("error deleting {}: {}", duplicate.path.display(), e);
+                state.record_failed(&duplicate.path, &e);
+                continue;
+            }
+            freed += options.freed_size(&duplicate.stat);
+            deleted += 1;
+            state.record_deleted(&duplicate.path, &duplicate.stat, options);
+        }
+    }
+    println!("deduped {} files, freeing {}", deleted, format_size(freed));
+}
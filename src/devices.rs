@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// which filesystems a run may touch, for `--device-whitelist` and `--device-blacklist`
+#[derive(Default)]
+pub struct Devices {
+    /// only these, if there's a whitelist. mount points of them are gone into
+    pub allowed: Option<HashSet<u64>>,
+    /// never these, even if they're whitelisted too
+    pub denied: HashSet<u64>,
+}
+
+impl Devices {
+    /// reads a comma separated list of devices, each a path to something on the filesystem
+    /// (usually where it's mounted) or its `major:minor`
+    pub fn parse_list(list: &str) -> Result<HashSet<u64>, String> {
+        list.split(',').filter(|d| !d.is_empty()).map(parse_device).collect()
+    }
+
+    /// returns true if what's on `dev` can be deleted
+    pub fn allows(&self, dev: u64) -> bool {
+        !self.denied.contains(&dev) && self.allowed.as_ref().is_none_or(|allowed| allowed.contains(&dev))
+    }
+
+    /// returns true if a mount point of `dev` should be gone into rather than stopped at,
+    /// which is only for the ones that were whitelisted by name
+    pub fn crosses_into(&self, dev: u64) -> bool {
+        self.allowed.as_ref().is_some_and(|allowed| allowed.contains(&dev)) && !self.denied.contains(&dev)
+    }
+}
+
+fn parse_device(device: &str) -> Result<u64, String> {
+    if let Some((major, minor)) = device.split_once(':') {
+        if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+            return Ok(libc::makedev(major, minor));
+        }
+    }
+    match std::fs::metadata(device) {
+        Ok(metadata) => Ok(metadata.dev()),
+        Err(e) => Err(format!("couldn't look at {}: {}", device, e)),
+    }
+}
+
+/// how a device is shown, as its `major:minor`
+pub fn format_dev(dev: u64) -> String {
+    format!("{}:{}", libc::major(dev), libc::minor(dev))
+}
+
+/// where `dev` is mounted, if it's anywhere we can find
+pub fn mount_point_of(dev: u64) -> Option<std::path::PathBuf> {
+    crate::mount_points()?
+        .into_iter()
+        .find(|m| Path::new(m).metadata().is_ok_and(|metadata| metadata.dev() == dev))
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::plan::{plan, PlannedAction};
+use crate::DeleteOptions;
+
+/// everything a run starting at `path` would delete, sorted
+pub fn planned(path: &Path, options: &DeleteOptions) -> BTreeSet<PathBuf> {
+    plan(path, options)
+        .filter_map(|action| match action {
+            PlannedAction::Delete(path) | PlannedAction::RemoveDir(path) => Some(path),
+            _ => None,
+        })
+        .collect()
+}
+
+/// reads a plan saved by `save`, or `None` if there isn't one yet
+pub fn load(file: &Path) -> io::Result<Option<BTreeSet<PathBuf>>> {
+    let contents = match std::fs::read(file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    // nul separated, since that's the only thing a path can't have in it
+    Ok(Some(contents.split(|b| *b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(std::ffi::OsStr::from_bytes(path)))
+        .collect()))
+}
+
+pub fn save(file: &Path, planned: &BTreeSet<PathBuf>) -> io::Result<()> {
+    let mut out = io::BufWriter::new(std::fs::File::create(file)?);
+    for path in planned {
+        out.write_all(path.as_os_str().as_bytes())?;
+        out.write_all(b"\0")?;
+    }
+    out.flush()
+}
+
+/// prints what's changed between a saved plan and a new one like a unified diff
+pub fn print(file: &Path, saved: &BTreeSet<PathBuf>, planned: &BTreeSet<PathBuf>) {
+    let mut lines = Vec::new();
+    // both are sorted, so going through them together keeps the output sorted too
+    let (mut old, mut new) = (saved.iter().peekable(), planned.iter().peekable());
+    loop {
+        match (old.peek(), new.peek()) {
+            (Some(a), Some(b)) if a == b => {
+                old.next();
+                new.next();
+            }
+            (Some(a), b) if b.is_none_or(|b| a < b) => {
+                lines.push(format!("-{}", a.display()));
+                old.next();
+            }
+            (_, Some(b)) => {
+                lines.push(format!("+{}", b.display()));
+                new.next();
+            }
+            _ => break,
+        }
+    }
+    if lines.is_empty() {
+        println!("no changes from {}", file.display());
+        return;
+    }
+    println!("--- {}", file.display());
+    println!("+++ this run");
+    for line in lines {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,291 @@
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::prelude::*;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// the result of an `lstat`/`fstatat`, we use this instead of `std::fs::Metadata` because
+/// the standard library has no way of making one relative to a directory fd
+#[derive(Copy, Clone)]
+pub struct Stat(libc::stat);
+
+impl std::fmt::Debug for Stat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Stat {{ dev: {}, ino: {}, mode: {:o}, size: {} }}", self.dev(), self.ino(), self.0.st_mode, self.size())
+    }
+}
+
+fn timestamp(secs: i64, nsecs: i64) -> SystemTime {
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::new(secs as u64, nsecs as u32)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + Duration::from_nanos(nsecs as u64)
+    }
+}
+
+// the types of the stat fields differ between platforms, so the casts are needed somewhere
+#[allow(clippy::unnecessary_cast)]
+impl Stat {
+    fn kind(&self) -> u32 {
+        self.0.st_mode as u32 & libc::S_IFMT as u32
+    }
+    pub fn is_dir(&self) -> bool {
+        self.kind() == libc::S_IFDIR as u32
+    }
+    pub fn is_file(&self) -> bool {
+        self.kind() == libc::S_IFREG as u32
+    }
+    pub fn is_symlink(&self) -> bool {
+        self.kind() == libc::S_IFLNK as u32
+    }
+    /// what kind of special file this is, for device nodes, sockets and fifos
+    pub fn special_kind(&self) -> Option<&'static str> {
+        match self.kind() {
+            k if k == libc::S_IFCHR as u32 => Some("character device"),
+            k if k == libc::S_IFBLK as u32 => Some("block device"),
+            k if k == libc::S_IFSOCK as u32 => Some("socket"),
+            k if k == libc::S_IFIFO as u32 => Some("fifo"),
+            _ => None,
+        }
+    }
+    /// the permission bits, without the file type
+    pub fn mode(&self) -> u32 {
+        self.0.st_mode as u32 & 0o7777
+    }
+    pub fn uid(&self) -> u32 {
+        self.0.st_uid as u32
+    }
+    pub fn gid(&self) -> u32 {
+        self.0.st_gid as u32
+    }
+    pub fn dev(&self) -> u64 {
+        self.0.st_dev as u64
+    }
+    pub fn ino(&self) -> u64 {
+        self.0.st_ino as u64
+    }
+    /// how many names the file has
+    pub fn nlink(&self) -> u64 {
+        self.0.st_nlink as u64
+    }
+    pub fn size(&self) -> u64 {
+        self.0.st_size as u64
+    }
+    /// how much space the file actually takes up on disk, which is less than `size` for
+    /// sparse files
+    pub fn allocated(&self) -> u64 {
+        self.0.st_blocks as u64 * 512
+    }
+    pub fn modified(&self) -> SystemTime {
+        timestamp(self.0.st_mtime as i64, self.0.st_mtime_nsec as i64)
+    }
+    pub fn accessed(&self) -> SystemTime {
+        timestamp(self.0.st_atime as i64, self.0.st_atime_nsec as i64)
+    }
+    /// when the inode last changed, which can't be set from outside like the other two
+    pub fn changed(&self) -> SystemTime {
+        timestamp(self.0.st_ctime as i64, self.0.st_ctime_nsec as i64)
+    }
+}
+
+pub(crate) fn cstr(name: &OsStr) -> io::Result<CString> {
+    CString::new(name.as_bytes()).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+pub(crate) fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// an open directory. everything is done relative to it with the `*at` syscalls and
+/// `AT_SYMLINK_NOFOLLOW`, so swapping a directory for a symlink halfway through a run
+/// can't make us operate on something outside of the tree we checked
+pub struct Dir {
+    fd: OwnedFd,
+}
+
+impl AsRawFd for Dir {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Dir {
+    const FLAGS: libc::c_int = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+
+    /// opens a directory by path, this is only used for the parent of where we start
+    pub fn open(path: &Path) -> io::Result<Dir> {
+        let path = cstr(path.as_os_str())?;
+        let fd = check(unsafe { libc::open(path.as_ptr(), Self::FLAGS) })?;
+        Ok(Dir { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// opens a subdirectory, refusing to go through a symlink unless `follow` is set
+    pub fn open_child(&self, name: &OsStr, follow: bool) -> io::Result<Dir> {
+        let name = cstr(name)?;
+        let flags = if follow { Self::FLAGS } else { Self::FLAGS | libc::O_NOFOLLOW };
+        let fd = check(unsafe { libc::openat(self.fd.as_raw_fd(), name.as_ptr(), flags) })?;
+        Ok(Dir { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// another handle on the same directory, for handing to another thread
+    pub fn try_clone(&self) -> io::Result<Dir> {
+        Ok(Dir { fd: self.fd.try_clone()? })
+    }
+
+    /// stats the directory itself
+    pub fn stat(&self) -> io::Result<Stat> {
+        let mut buf = MaybeUninit::<libc::stat>::uninit();
+        check(unsafe { libc::fstat(self.fd.as_raw_fd(), buf.as_mut_ptr()) })?;
+        Ok(Stat(unsafe { buf.assume_init() }))
+    }
+
+    /// returns true if we're allowed to add and remove entries here, going by the effective
+    /// uid the way unlink does
+    pub fn writable(&self) -> bool {
+        let flags = libc::AT_EACCESS;
+        unsafe { libc::faccessat(self.fd.as_raw_fd(), c".".as_ptr(), libc::W_OK | libc::X_OK, flags) == 0 }
+    }
+
+    /// stats an entry in the directory without following symlinks
+    pub fn stat_child(&self, name: &OsStr) -> io::Result<Stat> {
+        self.stat_child_inner(name, libc::AT_SYMLINK_NOFOLLOW)
+    }
+
+    /// stats whatever an entry in the directory points to
+    pub fn stat_child_follow(&self, name: &OsStr) -> io::Result<Stat> {
+        self.stat_child_inner(name, 0)
+    }
+
+    fn stat_child_inner(&self, name: &OsStr, flags: libc::c_int) -> io::Result<Stat> {
+        let name = cstr(name)?;
+        let mut buf = MaybeUninit::<libc::stat>::uninit();
+        check(unsafe { libc::fstatat(self.fd.as_raw_fd(), name.as_ptr(), buf.as_mut_ptr(), flags) })?;
+        Ok(Stat(unsafe { buf.assume_init() }))
+    }
+
+    /// lists the names in the directory, not including `.` and `..`
+    pub fn entries(&self) -> io::Result<Vec<OsString>> {
+        // closedir closes the fd it was given, so give it its own
+        let fd = check(unsafe { libc::fcntl(self.fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) })?;
+        let dir = unsafe { libc::fdopendir(fd) };
+        if dir.is_null() {
+            let e = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+        // the dup shares a position with our fd, so start from the top in case we've read it before
+        unsafe { libc::rewinddir(dir) };
+        let mut names = Vec::new();
+        loop {
+            let entry = unsafe { libc::readdir(dir) };
+            if entry.is_null() {
+                break;
+            }
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+            if name != b"." && name != b".." {
+                names.push(OsString::from_vec(name.to_vec()));
+            }
+        }
+        unsafe { libc::closedir(dir) };
+        Ok(names)
+    }
+
+    /// reads what a symlink in the directory points to
+    pub fn read_link(&self, name: &OsStr) -> io::Result<std::path::PathBuf> {
+        let name = cstr(name)?;
+        let mut buf = vec![0u8; libc::PATH_MAX as usize];
+        let len = unsafe { libc::readlinkat(self.fd.as_raw_fd(), name.as_ptr(), buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        buf.truncate(len as usize);
+        Ok(OsString::from_vec(buf).into())
+    }
+
+    /// opens an entry of any kind but a symlink for reading, without blocking on fifos
+    pub fn open_child_file(&self, name: &OsStr) -> io::Result<std::fs::File> {
+        let name = cstr(name)?;
+        let flags = libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_NONBLOCK | libc::O_CLOEXEC;
+        let fd = check(unsafe { libc::openat(self.fd.as_raw_fd(), name.as_ptr(), flags) })?;
+        Ok(std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    /// makes a new file in the directory with the permission bits `mode`, failing if the name
+    /// is already taken. the umask doesn't apply
+    pub fn create_file(&self, name: &OsStr, mode: u32) -> io::Result<std::fs::File> {
+        let name = cstr(name)?;
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        let fd = check(unsafe { libc::openat(self.fd.as_raw_fd(), name.as_ptr(), flags, mode as libc::c_uint) })?;
+        let file = std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+        Ok(file)
+    }
+
+    /// unlinks a non-directory entry
+    pub fn unlink(&self, name: &OsStr) -> io::Result<()> {
+        let name = cstr(name)?;
+        check(unsafe { libc::unlinkat(self.fd.as_raw_fd(), name.as_ptr(), 0) }).map(|_| ())
+    }
+
+    /// removes an empty subdirectory
+    pub fn remove_dir(&self, name: &OsStr) -> io::Result<()> {
+        let name = cstr(name)?;
+        check(unsafe { libc::unlinkat(self.fd.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR) }).map(|_| ())
+    }
+
+    /// swaps two entries in the directory in one go, so there's never a moment where either
+    /// name is missing
+    pub fn exchange(&self, a: &OsStr, b: &OsStr) -> io::Result<()> {
+        let a = cstr(a)?;
+        let b = cstr(b)?;
+        #[cfg(target_os = "linux")]
+        {
+            let fd = self.fd.as_raw_fd();
+            check(unsafe { libc::renameat2(fd, a.as_ptr(), fd, b.as_ptr(), libc::RENAME_EXCHANGE) }).map(|_| ())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (a, b);
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+    }
+
+    /// renames an entry within the directory, failing with `AlreadyExists` rather than
+    /// replacing whatever already has the new name
+    pub fn rename_noreplace(&self, from: &OsStr, to: &OsStr) -> io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        #[cfg(target_os = "linux")]
+        {
+            let (from, to) = (cstr(from)?, cstr(to)?);
+            check(unsafe { libc::renameat2(fd, from.as_ptr(), fd, to.as_ptr(), libc::RENAME_NOREPLACE) }).map(|_| ())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            // there's no doing it in one go here, so something could take the name in between
+            if self.stat_child(to).is_ok() {
+                return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+            }
+            let (from, to) = (cstr(from)?, cstr(to)?);
+            check(unsafe { libc::renameat(fd, from.as_ptr(), fd, to.as_ptr()) }).map(|_| ())
+        }
+    }
+
+    /// makes a hardlink to an entry somewhere else entirely
+    pub fn link_out(&self, name: &OsStr, to: &Path) -> io::Result<()> {
+        let name = cstr(name)?;
+        let to = cstr(to.as_os_str())?;
+        check(unsafe { libc::linkat(self.fd.as_raw_fd(), name.as_ptr(), libc::AT_FDCWD, to.as_ptr(), 0) }).map(|_| ())
+    }
+
+    /// renames an entry to somewhere else entirely
+    pub fn rename_out(&self, name: &OsStr, to: &Path) -> io::Result<()> {
+        let name = cstr(name)?;
+        let to = cstr(to.as_os_str())?;
+        check(unsafe { libc::renameat(self.fd.as_raw_fd(), name.as_ptr(), libc::AT_FDCWD, to.as_ptr()) }).map(|_| ())
+    }
+}
@@ -0,0 +1,35 @@
+use std::io;
+
+/// the kinds of errors we can run into while deleting, from least to most severe
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ErrorCategory {
+    /// something disappeared before we got to it, it's gone either way
+    NotFound,
+    /// something was in use, trying again later might work
+    Busy,
+    /// we weren't allowed to, someone needs to sudo or chmod
+    Permission,
+    /// anything else
+    Other,
+}
+
+impl ErrorCategory {
+    pub fn of(e: &io::Error) -> ErrorCategory {
+        match e.raw_os_error() {
+            Some(libc::ENOENT) => ErrorCategory::NotFound,
+            Some(libc::EBUSY) | Some(libc::ETXTBSY) => ErrorCategory::Busy,
+            Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => ErrorCategory::Permission,
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    /// the process exit code for a run whose worst error was this, see the README
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCategory::NotFound => 3,
+            ErrorCategory::Busy => 4,
+            ErrorCategory::Permission => 5,
+            ErrorCategory::Other => 6,
+        }
+    }
+}
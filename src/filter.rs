@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::os::unix::prelude::*;
+use std::path::Path;
+use regex::bytes::{Regex, RegexBuilder};
+use unicode_normalization::UnicodeNormalization;
+use crate::SkipReason;
+
+/// which unicode form names and patterns are put in before matching, for `--normalize-unicode`.
+/// macOS keeps names decomposed, so a pattern typed composed doesn't match them as they are
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Normalization {
+    #[default]
+    None,
+    Nfc,
+    Nfd,
+}
+
+impl Normalization {
+    pub fn parse(form: &str) -> Option<Normalization> {
+        match form {
+            "none" => Some(Normalization::None),
+            "nfc" => Some(Normalization::Nfc),
+            "nfd" => Some(Normalization::Nfd),
+            _ => None,
+        }
+    }
+
+    /// puts `text` in this form, names that aren't utf-8 can't be normalized and are left as they are
+    pub fn apply<'a>(&self, text: &'a [u8]) -> Cow<'a, [u8]> {
+        match (self, std::str::from_utf8(text)) {
+            (Normalization::Nfc, Ok(text)) => Cow::Owned(text.nfc().collect::<String>().into_bytes()),
+            (Normalization::Nfd, Ok(text)) => Cow::Owned(text.nfd().collect::<String>().into_bytes()),
+            _ => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// builds one of the patterns the filters match with, in the form `normalize` puts names in,
+/// and ignoring case if `ignore_case` is set, for `--ignore-case`
+pub fn compile(pattern: &str, normalize: Normalization, ignore_case: bool) -> Result<Regex, regex::Error> {
+    let pattern = String::from_utf8_lossy(&normalize.apply(pattern.as_bytes())).into_owned();
+    RegexBuilder::new(&pattern).case_insensitive(ignore_case).build()
+}
+
+/// predicates on which files get deleted, every one of them has to pass.
+/// directories are always gone into, so these only ever decide about files
+#[derive(Default)]
+pub struct Filters {
+    /// only delete files matching this
+    pub regex: Option<Regex>,
+    /// never delete files matching this
+    pub exclude_regex: Option<Regex>,
+    /// only delete files at this depth if they match, for `--include-at-depth`
+    pub include_at_depth: Option<(usize, Regex)>,
+    /// never delete files at this depth that match, for `--exclude-at-depth`
+    pub exclude_at_depth: Option<(usize, Regex)>,
+    /// match the regexes against the full path instead of just the name
+    pub regex_full_path: bool,
+    /// only delete files git ignores
+    pub gitignore: Option<crate::gitignore::GitIgnore>,
+    /// never delete these, they're the files git tracks, for `--refuse-if-git-tracked`
+    pub git_tracked: Option<std::collections::HashSet<std::path::PathBuf>>,
+    /// the form names are normalized to before the regexes see them, the patterns are expected
+    /// to be in it already
+    pub normalize: Normalization,
+    /// the patterns were built ignoring case, and the globs typed in with
+    /// `--interactive-filter-repl` are too
+    pub ignore_case: bool,
+}
+
+impl Filters {
+    /// returns true if there aren't any filters, so everything passes
+    pub fn is_empty(&self) -> bool {
+        self.regex.is_none()
+            && self.exclude_regex.is_none()
+            && self.include_at_depth.is_none()
+            && self.exclude_at_depth.is_none()
+            && self.gitignore.is_none()
+            && self.git_tracked.is_none()
+    }
+
+    /// returns why a file should be left alone, if it should be. `depth` is how far below the
+    /// starting path it is, with what's directly in it at 1
+    pub fn check(&self, name: &OsStr, path: &Path, depth: usize) -> Option<SkipReason> {
+        // matching on bytes so names that aren't utf-8 still work
+        let subject = if self.regex_full_path { path.as_os_str().as_bytes() } else { name.as_bytes() };
+        let subject = &*self.normalize.apply(subject);
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(subject) {
+                return Some(SkipReason::NotMatched);
+            }
+        }
+        if let Some(regex) = &self.exclude_regex {
+            if regex.is_match(subject) {
+                return Some(SkipReason::Excluded);
+            }
+        }
+        if let Some((at, regex)) = &self.include_at_depth {
+            if depth == *at && !regex.is_match(subject) {
+                return Some(SkipReason::NotMatchedAtDepth(depth));
+            }
+        }
+        if let Some((at, regex)) = &self.exclude_at_depth {
+            if depth == *at && regex.is_match(subject) {
+                return Some(SkipReason::ExcludedAtDepth(depth));
+            }
+        }
+        if let Some(gitignore) = &self.gitignore {
+            if gitignore.is_tracked(path) {
+                return Some(SkipReason::Tracked);
+            }
+            if !gitignore.is_ignored(path) {
+                return Some(SkipReason::NotIgnored);
+            }
+        }
+        if self.git_tracked.as_ref().is_some_and(|tracked| tracked.contains(path)) {
+            return Some(SkipReason::Tracked);
+        }
+        None
+    }
+}
@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+
+/// the files git tracks in the repository at `root`, relative to it
+fn ls_files(root: &Path) -> io::Result<impl Iterator<Item = PathBuf>> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("git ls-files failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    let files: Vec<_> = output.stdout.split(|&b| b == 0)
+        .filter(|f| !f.is_empty())
+        .map(|f| PathBuf::from(OsStr::from_bytes(f)))
+        .collect();
+    Ok(files.into_iter())
+}
+
+/// the files git tracks under `start`, written the way the walk reaches them, for
+/// `--refuse-if-git-tracked`. None if `start` isn't in a repository. the repository is
+/// looked for from the canonical path, so it's found wherever we're run from
+pub fn tracked_under(start: &Path) -> io::Result<Option<HashSet<PathBuf>>> {
+    let canonical = start.canonicalize()?;
+    let Some(root) = canonical.ancestors().find(|a| a.join(".git").symlink_metadata().is_ok()) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        ls_files(root)?
+            .filter_map(|f| root.join(f).strip_prefix(&canonical).ok().map(|rest| start.join(rest)))
+            .collect(),
+    ))
+}
+
+/// the `.gitignore` files of a repository, for deleting only what git ignores like `git clean -X`
+pub struct GitIgnore {
+    /// the top of the repository, `.gitignore` files above it don't count
+    root: PathBuf,
+    /// the `.gitignore` of every directory we've looked in so far
+    matchers: RefCell<HashMap<PathBuf, Gitignore>>,
+    /// files git is tracking, these are never deleted even if a pattern matches them
+    tracked: HashSet<PathBuf>,
+}
+
+impl GitIgnore {
+    /// finds the repository `start` is in and asks git which files it tracks. if `start` isn't
+    /// in a repository that's an error unless `force` is set, then `start` is treated as the top
+    pub fn open(start: &Path, force: bool) -> io::Result<GitIgnore> {
+        let root = match start.ancestors().find(|a| a.join(".git").symlink_metadata().is_ok()) {
+            Some(root) => root.to_path_buf(),
+            None if force => {
+                return Ok(GitIgnore { root: start.to_path_buf(), matchers: RefCell::default(), tracked: HashSet::new() });
+            }
+            None => return Err(io::Error::other(format!("{} isn't inside a git repository", start.display()))),
+        };
+        let tracked = ls_files(&root)?.map(|f| root.join(f)).collect();
+        Ok(GitIgnore { root, matchers: RefCell::default(), tracked })
+    }
+
+    pub fn is_tracked(&self, path: &Path) -> bool {
+        self.tracked.contains(path)
+    }
+
+    /// returns what the `.gitignore` files from `path`'s directory up to the top of the
+    /// repository say about it, the closest one that has an opinion wins
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let mut matchers = self.matchers.borrow_mut();
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) {
+                break;
+            }
+            let matcher = matchers.entry(dir.to_path_buf())
+                .or_insert_with(|| Gitignore::new(dir.join(".gitignore")).0);
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// returns true if git would ignore the file at `path`
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        // nothing in an ignored directory can be brought back, so check those first
+        let in_ignored_dir = path.ancestors().skip(1)
+            .take_while(|dir| *dir != self.root && dir.starts_with(&self.root))
+            .any(|dir| self.matches(dir, true));
+        in_ignored_dir || self.matches(path, false)
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::Path;
+use crate::dirfd::Stat;
+use crate::plan_file::json_string;
+use crate::{DirSummary, SkipReason};
+
+/// a json object on a line of its own for everything done as it's done, for `--jsonl`. they go
+/// to where stdout was when we started, and everything else is printed to stderr instead
+pub struct Jsonl {
+    out: std::fs::File,
+    /// add the device and inode of each entry to its record, for `--show-ids`
+    pub ids: bool,
+}
+
+impl Jsonl {
+    /// keeps stdout for the records and points fd 1 at stderr, so nothing printed the usual
+    /// way ends up in between them
+    pub fn take_stdout() -> io::Result<Jsonl> {
+        io::stdout().flush()?;
+        let fd = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 3) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let out = std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Jsonl { out, ids: false })
+    }
+
+    /// something that was deleted, trashed or moved, `action` saying which
+    pub fn removed(&self, path: &Path, stat: &Stat, action: &str, dryrun: bool) {
+        let kind = if stat.is_dir() {
+            "dir"
+        } else if stat.is_symlink() {
+            "symlink"
+        } else {
+            stat.special_kind().unwrap_or("file")
+        };
+        let mtime = match stat.modified().duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => after.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        };
+        self.emit(format!(
+            "{{\"action\":\"{}\",\"path\":{},\"type\":\"{}\",\"size\":{},\"uid\":{},\"mtime\":{:.3},\"dryrun\":{}{}}}",
+            action, json_string(&path.to_string_lossy()), kind, stat.size(), stat.uid(), mtime, dryrun, self.ids_of(Some(stat)),
+        ));
+    }
+
+    /// the `dev` and `ino` fields for `--show-ids`, if there's a stat to take them from
+    fn ids_of(&self, stat: Option<&Stat>) -> String {
+        match stat.filter(|_| self.ids) {
+            Some(stat) => format!(",\"dev\":{},\"ino\":{}", stat.dev(), stat.ino()),
+            None => String::new(),
+        }
+    }
+
+    /// a directory we're going into, with `--walk-order preorder`
+    pub fn entered(&self, path: &Path) {
+        self.emit(format!("{{\"action\":\"enter\",\"path\":{},\"type\":\"dir\"}}", json_string(&path.to_string_lossy())));
+    }
+
+    pub fn skipped(&self, path: &Path, stat: Option<&Stat>, reason: &SkipReason) {
+        self.emit(format!(
+            "{{\"action\":\"skip\",\"path\":{},\"reason\":\"{}\",\"message\":{}{}}}",
+            json_string(&path.to_string_lossy()), reason.name(), json_string(&reason.to_string()), self.ids_of(stat),
+        ));
+    }
+
+    /// what happened in a directory once it's done with, for `--dir-summary`
+    pub(crate) fn dir_summary(&self, path: &Path, summary: &DirSummary) {
+        self.emit(format!(
+            "{{\"action\":\"dir_summary\",\"path\":{},\"files\":{},\"dirs\":{},\"symlinks\":{},\"files_skipped\":{},\"dirs_skipped\":{},\"symlinks_skipped\":{}}}",
+            json_string(&path.to_string_lossy()), summary.files, summary.dirs, summary.symlinks, summary.files_skipped, summary.dirs_skipped, summary.symlinks_skipped,
+        ));
+    }
+
+    /// writes a record in one go, so a reader never sees half of one
+    fn emit(&self, mut line: String) {
+        line.push('\n');
+        if let Err(e) = (&self.out).write_all(line.as_bytes()) {
+            log::warn!("couldn't write a --jsonl record: {}", e);
+        }
+    }
+}
@@ -0,0 +1,1962 @@
+use std::ffi::OsStr;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use dirfd::{Dir, Stat};
+
+pub mod age;
+#[cfg(target_os = "linux")]
+pub mod attrs;
+pub mod backup;
+pub mod bench;
+pub mod checkpoint;
+#[cfg(feature = "compress")]
+pub mod compress;
+pub mod copier;
+pub mod dedupe;
+pub mod devices;
+pub mod diff;
+pub mod dirfd;
+pub mod error;
+pub mod filter;
+pub mod gitignore;
+pub mod jsonl;
+pub mod lock;
+pub mod manifest;
+pub mod move_to;
+pub mod open_files;
+pub mod owner;
+pub mod partition;
+pub mod path_filter;
+pub mod permissions;
+pub mod plan;
+pub mod plan_file;
+pub mod prefetch;
+pub mod pseudo_fs;
+pub mod quota;
+pub mod preview;
+pub mod reflink;
+pub mod rename;
+pub mod repl;
+pub mod review;
+pub mod selftest;
+#[cfg(all(target_os = "linux", feature = "selinux"))]
+pub mod selinux;
+#[cfg(any(debug_assertions, feature = "simulate-errors"))]
+pub mod simulate;
+pub mod space;
+pub mod suggest;
+pub mod summary;
+pub mod swap;
+#[cfg(all(unix, feature = "syslog"))]
+pub mod syslog;
+pub mod targets;
+pub mod trash;
+pub mod tree;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+pub mod verify;
+
+/// what to do with files and directories whose names start with a `.`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Dotfiles {
+    Skip,
+    Delete,
+    /// ask about each one as we get to it
+    Prompt,
+}
+
+impl Dotfiles {
+    pub fn parse(policy: &str) -> Option<Dotfiles> {
+        match policy {
+            "skip" => Some(Dotfiles::Skip),
+            "delete" => Some(Dotfiles::Delete),
+            "prompt" => Some(Dotfiles::Prompt),
+            _ => None,
+        }
+    }
+}
+
+/// when a directory gets reported, for `--walk-order`. it's always removed after its contents,
+/// this is only about what's printed and written to `--jsonl`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WalkOrder {
+    /// say so when going into a directory, before anything in it
+    Preorder,
+    /// only once it's done with, after everything in it
+    Postorder,
+}
+
+impl WalkOrder {
+    pub fn parse(order: &str) -> Option<WalkOrder> {
+        match order {
+            "preorder" => Some(WalkOrder::Preorder),
+            "postorder" => Some(WalkOrder::Postorder),
+            _ => None,
+        }
+    }
+}
+
+/// what to do on finding something mounted below the starting path
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mountpoints {
+    Skip,
+    Unmount,
+    /// stop the whole run
+    Fail,
+}
+
+impl Mountpoints {
+    pub fn parse(policy: &str) -> Option<Mountpoints> {
+        match policy {
+            "skip" => Some(Mountpoints::Skip),
+            "unmount" => Some(Mountpoints::Unmount),
+            "fail" | "descend-error" => Some(Mountpoints::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// what to do when unmounting something fails, for `--unmount-failure`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UnmountFailure {
+    /// stop the whole run
+    Abort,
+    /// leave the mount point and everything on it, and carry on
+    Skip,
+}
+
+impl UnmountFailure {
+    pub fn parse(policy: &str) -> Option<UnmountFailure> {
+        match policy {
+            "abort" => Some(UnmountFailure::Abort),
+            "skip" => Some(UnmountFailure::Skip),
+            _ => None,
+        }
+    }
+}
+
+pub struct DeleteOptions {
+    pub recursive: bool,
+    pub mountpoints: Mountpoints,
+    /// unmount strictly and do this when it fails, without it busy mounts are detached lazily
+    /// and a run stops if even that fails
+    pub unmount_failure: Option<UnmountFailure>,
+    pub dryrun: bool,
+    pub allow_delete_above_start: bool,
+    /// report entries that something else removed while we were walking as errors, rather
+    /// than skipping them
+    pub strict_canonicalize: bool,
+    pub enter_symlinks: bool,
+    pub verbose: bool,
+    pub dotfiles: Dotfiles,
+    pub remove_symlinks: bool,
+    pub dir_summary: bool,
+    pub trash: Option<trash::Trash>,
+    pub backup: Option<backup::Backup>,
+    /// move files here instead of deleting them
+    pub move_to: Option<move_to::MoveTo>,
+    /// rename files where they are instead of deleting them, for `--rename`
+    pub rename: Option<rename::Rename>,
+    pub exclude_newer_than_start: bool,
+    /// only delete things that haven't been used in a while, for `--tmpfiles`
+    pub age: Option<age::Age>,
+    /// keep files used more recently than this, for `--not-accessed-since`
+    pub not_accessed_since: Option<age::Age>,
+    pub filters: filter::Filters,
+    /// only delete things at least this far below the starting path, the starting path is 0
+    pub min_depth: usize,
+    /// empty the starting directory but don't remove it
+    pub preserve_top: bool,
+    /// when directories are reported, for `--walk-order`. without it `-v` says when we go into one
+    pub walk_order: Option<WalkOrder>,
+    /// keep the newest file that would be deleted in each directory, for
+    /// `--preserve-newest-in-each-dir`
+    pub preserve_newest: bool,
+    /// start on a mount point and clean it, but don't go into anything mounted below it
+    pub no_descend_mountpoints: bool,
+    /// count freed space by the blocks files take up instead of their length, for `--accurate-size`
+    pub accurate_size: bool,
+    /// also delete what removed symlinks pointed to, for `--with-targets`
+    pub with_targets: bool,
+    /// check everything we removed is gone once we're done, for `--two-phase`
+    pub two_phase: bool,
+    /// show what's in each directory and ask before going into it, for `--confirm-each-dir-with-preview`
+    pub confirm_each_dir: bool,
+    /// stat a directory's entries a few at a time before going through them, for `--parallel-stat`
+    pub parallel_stat: bool,
+    /// print every check each entry goes through, for `--trace-decisions`
+    pub trace_decisions: bool,
+    /// the device the starting path is on, special files anywhere else are left alone, for
+    /// `--skip-device-files-on-other-fs`
+    pub device_files_dev: Option<u64>,
+    /// the canonical starting directory, any symlink below it pointing outside of it stops the
+    /// run, for `--abort-on-symlink-escape`
+    pub abort_on_symlink_escape: Option<PathBuf>,
+    /// how long the whole run gets before we stop, for `--deadline`
+    pub deadline: Option<std::time::Duration>,
+    /// only remove symlinks whose target doesn't exist
+    pub prune_broken_symlinks: bool,
+    /// the owner of the starting directory, directories anyone else owns aren't gone into. on
+    /// by default for root, for `--no-cross-user-dirs`
+    pub dir_owner: Option<u32>,
+    /// only delete files this uid owns in directories it can write to, for
+    /// `--only-if-owned-and-writable`
+    pub owned_and_writable: Option<u32>,
+    /// which filesystems can be touched, for `--device-whitelist` and `--device-blacklist`
+    pub devices: Option<devices::Devices>,
+    /// put the device and inode of each entry next to it in what's printed, for `--show-ids`
+    pub show_ids: bool,
+    /// only delete what this uid owns, for `--owner`
+    pub owner: Option<u32>,
+    /// only delete what this gid owns, for `--group`
+    pub group: Option<u32>,
+    /// the only paths that may be deleted, as left in the editor by `--interactive-editor`
+    pub selected: Option<std::collections::HashSet<PathBuf>>,
+    /// what to put in a new file in place of each deleted one, for `--replace-with-placeholder`
+    pub placeholder: Option<Vec<u8>>,
+    /// clear the immutable and append-only attributes of what can't be removed because of them
+    pub clear_attrs: bool,
+    /// fsync the directories things were removed from after this many removals, for `--sync-every`
+    pub sync_every: Option<usize>,
+    /// directories to leave in place once they're emptied, from `--keep-structure-file`
+    pub keep_structure: Option<std::collections::HashSet<PathBuf>>,
+    /// a program that has to agree to each file being deleted, for `--path-filter`
+    pub path_filter: Option<path_filter::PathFilter>,
+    /// files other processes had open when we started, for `--skip-open`
+    pub open_files: Option<open_files::OpenFiles>,
+    /// where to record finished subtrees, for `--checkpoint`
+    pub checkpoint: Option<checkpoint::Checkpoint>,
+    /// where the hash of each deleted file is recorded, for `--hash-manifest`
+    pub hash_manifest: Option<manifest::Manifest>,
+    /// only delete until the filesystem has this much free, for `--until-free` and
+    /// `--until-free-inodes`, or until a quota project takes up less, for `--until-project-below`
+    pub until_free: Option<space::Goal>,
+    /// keep track of how much was freed in each quota project, for `--quota-aware`
+    pub quota_aware: bool,
+    /// only delete files that are duplicates of another one
+    pub dedupe: bool,
+    /// keep exact counts of what was deleted for a report at the end, which rules out the fast path
+    pub report: bool,
+    /// how many of the biggest deleted files to list at the end, for `--report-largest`
+    pub report_largest: usize,
+    /// list the deleted files that still have other names, for `--dangling-hardlink-report`
+    pub hardlink_report: bool,
+    /// fail some removals on purpose, for testing
+    #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+    pub simulate_errors: Option<simulate::SimulatedErrors>,
+    /// where files are queued up to be unlinked in batches, for `--io-uring`
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub io_uring: Option<std::cell::RefCell<uring::Ring>>,
+    pub starting_dir: PathBuf,
+    pub start_time: SystemTime,
+    /// every mount point on the system, if we could find out, used to rule out the fast path
+    pub mount_points: Option<Vec<PathBuf>>,
+    /// mount points under the start, written the way the walk reaches them, to catch bind
+    /// mounts that are on the same device as their parent. None goes by the device alone
+    pub bind_mounts: Option<std::collections::HashSet<PathBuf>>,
+    /// the devices of /proc, /sys and the like, with their filesystem types. nothing on them
+    /// is ever deleted, whatever else was asked for
+    pub pseudo_filesystems: std::collections::HashMap<u64, &'static str>,
+}
+
+impl DeleteOptions {
+    /// options with every check on and everything else off, for deleting things under `starting_dir`
+    pub fn new(starting_dir: PathBuf) -> DeleteOptions {
+        DeleteOptions {
+            recursive: false,
+            mountpoints: Mountpoints::Skip,
+            unmount_failure: None,
+            dryrun: false,
+            allow_delete_above_start: false,
+            strict_canonicalize: false,
+            enter_symlinks: false,
+            verbose: false,
+            dotfiles: Dotfiles::Skip,
+            remove_symlinks: false,
+            dir_summary: false,
+            trash: None,
+            backup: None,
+            move_to: None,
+            rename: None,
+            exclude_newer_than_start: false,
+            age: None,
+            not_accessed_since: None,
+            filters: filter::Filters::default(),
+            min_depth: 0,
+            preserve_top: false,
+            preserve_newest: false,
+            walk_order: None,
+            no_descend_mountpoints: false,
+            accurate_size: false,
+            with_targets: false,
+            two_phase: false,
+            confirm_each_dir: false,
+            parallel_stat: false,
+            trace_decisions: false,
+            device_files_dev: None,
+            abort_on_symlink_escape: None,
+            deadline: None,
+            prune_broken_symlinks: false,
+            dir_owner: None,
+            owned_and_writable: None,
+            devices: None,
+            show_ids: false,
+            owner: None,
+            group: None,
+            selected: None,
+            placeholder: None,
+            clear_attrs: false,
+            sync_every: None,
+            keep_structure: None,
+            path_filter: None,
+            open_files: None,
+            checkpoint: None,
+            hash_manifest: None,
+            until_free: None,
+            quota_aware: false,
+            dedupe: false,
+            report: false,
+            report_largest: 0,
+            hardlink_report: false,
+            #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+            simulate_errors: None,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            io_uring: None,
+            starting_dir,
+            start_time: SystemTime::now(),
+            mount_points: None,
+            bind_mounts: None,
+            pseudo_filesystems: pseudo_fs::mounted(),
+        }
+    }
+
+    /// returns true if no per-file decisions could possibly be made, so that whole subtrees
+    /// can be handed to `remove_dir_all` instead of being walked one entry at a time
+    pub fn fast_path_allowed(&self) -> bool {
+        self.recursive
+            && !self.dryrun
+            && !self.verbose
+            && !self.dir_summary
+            && self.allow_delete_above_start
+            && self.dotfiles == Dotfiles::Delete
+            && self.remove_symlinks
+            && self.trash.is_none()
+            && self.backup.is_none()
+            && self.move_to.is_none()
+            && self.rename.is_none()
+            && !self.preserve_newest
+            && self.walk_order != Some(WalkOrder::Preorder)
+            && !self.exclude_newer_than_start
+            && self.age.is_none()
+            && self.not_accessed_since.is_none()
+            && self.filters.is_empty()
+            && self.owner.is_none()
+            && self.dir_owner.is_none()
+            && self.owned_and_writable.is_none()
+            && self.devices.is_none()
+            && self.group.is_none()
+            && self.selected.is_none()
+            && self.keep_structure.is_none()
+            && self.placeholder.is_none()
+            && self.sync_every.is_none()
+            && !self.clear_attrs
+            && self.path_filter.is_none()
+            && self.open_files.is_none()
+            && self.checkpoint.is_none()
+            && self.hash_manifest.is_none()
+            && !self.hold_back()
+            && !self.report
+            && self.report_largest == 0
+            && !self.hardlink_report
+            && !self.quota_aware
+            && !self.prune_broken_symlinks
+            && self.deadline.is_none()
+            && self.abort_on_symlink_escape.is_none()
+            && self.device_files_dev.is_none()
+            && !self.trace_decisions
+            && !self.confirm_each_dir
+            && !self.simulating_errors()
+            && !self.batching_unlinks()
+    }
+
+    /// returns true if some removals are going to fail on purpose
+    fn simulating_errors(&self) -> bool {
+        #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+        return self.simulate_errors.is_some();
+        #[cfg(not(any(debug_assertions, feature = "simulate-errors")))]
+        return false;
+    }
+
+    /// returns true if files are unlinked through `--io-uring`, which only takes over when
+    /// removing a file is nothing more than unlinking it
+    fn batching_unlinks(&self) -> bool {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        return self.io_uring.as_ref().is_some_and(|ring| ring.borrow().usable())
+            && !self.dryrun
+            && self.trash.is_none()
+            && self.backup.is_none()
+            && self.move_to.is_none()
+            && self.rename.is_none()
+            && self.placeholder.is_none()
+            && self.hash_manifest.is_none()
+            && self.checkpoint.is_none()
+            && !self.clear_attrs
+            && !self.quota_aware
+            && !self.dir_summary
+            && !self.simulating_errors();
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        return false;
+    }
+
+    /// returns true if files that pass every check are held back in `RunState::candidates`
+    /// to be dealt with after the walk, instead of being deleted straight away
+    pub fn hold_back(&self) -> bool {
+        self.until_free.is_some() || self.dedupe
+    }
+
+    /// what removing a file does with it, for the records of each one
+    pub fn removal(&self) -> &'static str {
+        if self.move_to.is_some() {
+            "move"
+        } else if self.rename.is_some() {
+            "rename"
+        } else if self.trash.is_some() {
+            "trash"
+        } else {
+            "delete"
+        }
+    }
+
+    /// how much deleting a file frees, as far as the counts we keep go
+    pub fn freed_size(&self, stat: &Stat) -> u64 {
+        if self.accurate_size { stat.allocated() } else { stat.size() }
+    }
+
+    /// what `--owner` and `--group` say about `stat`, both have to match when both are given
+    pub fn check_ownership(&self, stat: &Stat) -> Option<SkipReason> {
+        if self.owner.is_some_and(|uid| stat.uid() != uid) {
+            return Some(SkipReason::OwnedBy(stat.uid()));
+        }
+        if self.group.is_some_and(|gid| stat.gid() != gid) {
+            return Some(SkipReason::InGroup(stat.gid()));
+        }
+        None
+    }
+
+    /// returns true if something is mounted on an entry. a bind mount of the same filesystem
+    /// keeps the device of its parent, so those can only be found by their path
+    pub(crate) fn is_mounted(&self, path: &Path, stat: &Stat, parent_dev: u64) -> bool {
+        is_mountpoint(stat, parent_dev) || self.bind_mounts.as_ref().is_some_and(|m| m.contains(path))
+    }
+
+    /// why a directory we went into has to stay, going by the filesystem it's on, for
+    /// `--device-whitelist` and `--device-blacklist`. a whitelisted mount point is gone into
+    /// but can't be removed itself
+    pub(crate) fn check_device(&self, path: &Path, stat: &Stat, parent_dev: u64) -> Option<SkipReason> {
+        let devices = self.devices.as_ref()?;
+        if !devices.allows(stat.dev()) {
+            Some(SkipReason::OnDevice(stat.dev()))
+        } else if self.is_mounted(path, stat, parent_dev) {
+            Some(SkipReason::MountPoint)
+        } else {
+            None
+        }
+    }
+
+    /// whether `path` is one of the directories `--keep-structure-file` says to keep
+    pub fn keeps_structure(&self, path: &Path) -> bool {
+        self.keep_structure.as_ref().is_some_and(|keep| keep.contains(path))
+    }
+
+    /// whether `path` was taken out of the list with `--interactive-editor`
+    pub fn deselected(&self, path: &Path) -> bool {
+        self.selected.as_ref().is_some_and(|selected| !selected.contains(path))
+    }
+
+    /// how `path` is shown in what's printed about it, with its device and inode after it for
+    /// `--show-ids`
+    pub fn display(&self, path: &Path, stat: &Stat) -> String {
+        if self.show_ids {
+            format!("{} [{}:{}]", path.display(), stat.dev(), stat.ino())
+        } else {
+            path.display().to_string()
+        }
+    }
+
+    /// returns true if a subtree can be removed in one go with `remove_dir_all`
+    fn can_fast_path(&self, path: &Path, depth: usize) -> bool {
+        match &self.mount_points {
+            Some(mount_points) if self.fast_path_allowed() && depth >= self.min_depth && !(self.preserve_top && depth == 0) => {
+                !mount_points.iter().any(|m| m.starts_with(path))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// a deleted file that had other names, for `--dangling-hardlink-report`
+pub struct Hardlinked {
+    /// the names of it we deleted
+    pub paths: Vec<PathBuf>,
+    /// the link counts it had when we looked at it, the count drops as we delete names of it
+    /// unless they were all looked at first
+    pub most_links: u64,
+    pub fewest_links: u64,
+    pub size: u64,
+}
+
+impl Hardlinked {
+    /// how many names it has left that we didn't delete, 0 if its data was freed
+    pub fn remaining(&self) -> u64 {
+        (self.fewest_links - 1).min(self.most_links.saturating_sub(self.paths.len() as u64))
+    }
+}
+
+/// things we keep track of over the course of a run
+#[derive(Default)]
+pub struct RunState {
+    /// the most severe kind of error we've run into so far
+    pub worst_error: Option<error::ErrorCategory>,
+    /// how many errors we've run into so far
+    pub errors: usize,
+    /// files held back to be dealt with afterwards, for `--until-free` and `--dedupe`
+    pub candidates: Vec<space::Candidate>,
+    /// files and symlinks deleted (or trashed, or moved), along with how big they were
+    pub files_deleted: usize,
+    pub bytes_freed: u64,
+    pub dirs_removed: usize,
+    /// how many things were skipped for each reason, by `SkipReason::name`
+    pub skipped: std::collections::BTreeMap<&'static str, usize>,
+    /// set once `--deadline` has passed, nothing else gets touched after that
+    pub stopped: bool,
+    /// set when the user stopped the run from a prompt, along with `stopped`
+    pub aborted: bool,
+    /// set along with `stopped` when `--abort-on-symlink-escape` found a symlink leading out
+    pub escaped: bool,
+    /// stop once this many errors have piled up, for `--max-errors`
+    pub max_errors: Option<usize>,
+    /// set along with `stopped` when that happened
+    pub tripped: bool,
+    /// answered "all" to `--confirm-each-dir-with-preview`, so stop asking
+    pub confirmed_all_dirs: bool,
+    /// symlinks we removed and the canonical paths they pointed to, for `--with-targets`
+    pub link_targets: Vec<(PathBuf, PathBuf)>,
+    /// everything we removed, in the order we removed it, kept for `--two-phase` to check on
+    pub removed: Vec<PathBuf>,
+    /// files whose copy into the trash failed in the background, reported at the end
+    pub failed_copies: Vec<PathBuf>,
+    /// how many things `--device-whitelist` and `--device-blacklist` skipped on each device
+    pub skipped_devices: std::collections::BTreeMap<u64, usize>,
+    /// how many things `--owner` skipped for each uid that owned them
+    pub skipped_owners: std::collections::BTreeMap<u32, usize>,
+    /// the same for `--group`, by gid
+    pub skipped_groups: std::collections::BTreeMap<u32, usize>,
+    /// the biggest files deleted so far with their sizes, smallest on top so it's the one that
+    /// goes when a bigger one comes along, for `--report-largest`
+    pub largest: std::collections::BinaryHeap<std::cmp::Reverse<(u64, PathBuf)>>,
+    /// deleted files that had other names when we looked at them, by device and inode, for
+    /// `--dangling-hardlink-report`
+    pub hardlinks: std::collections::BTreeMap<(u64, u64), Hardlinked>,
+    /// how much was freed in each quota project, for `--quota-aware`
+    pub projects: std::collections::BTreeMap<u32, u64>,
+    /// where a record of everything done goes as it's done, for `--jsonl`
+    pub jsonl: Option<jsonl::Jsonl>,
+    #[cfg(all(unix, feature = "syslog"))]
+    pub syslog: Option<syslog::Syslog>,
+    /// where what was deleted, skipped and failed is written, for `--partition-by-error`
+    pub partition: Option<partition::Partition>,
+    /// directories something was removed from since the last `--sync-every` sync
+    pub unsynced: std::collections::BTreeSet<PathBuf>,
+    /// how many things were removed since then
+    pub removed_since_sync: usize,
+}
+
+impl RunState {
+    pub fn record_error(&mut self, e: &std::io::Error) {
+        self.worst_error = self.worst_error.max(Some(error::ErrorCategory::of(e)));
+        self.errors += 1;
+        if let Some(max) = self.max_errors.filter(|&max| self.errors >= max && !self.stopped) {
+            println!("stopping, that's {} errors and --max-errors is {}", self.errors, max);
+            self.stopped = true;
+            self.tripped = true;
+        }
+    }
+
+    /// counts an error about `path`, which goes in the list of failures if we're keeping one
+    pub fn record_failed(&mut self, path: &Path, e: &std::io::Error) {
+        self.record_error(e);
+        if trash::is_full(e) && !self.stopped {
+            println!("stopping, --trash-fallback abort was given");
+            self.stopped = true;
+            self.aborted = true;
+        }
+        if let Some(partition) = &mut self.partition {
+            partition.failed(path);
+        }
+    }
+
+    /// reports and counts something being skipped
+    pub fn skip(&mut self, path: &Path, reason: SkipReason) {
+        self.skip_inner(path, path.display().to_string(), None, reason);
+    }
+
+    /// `skip` for an entry we've stat'd, which `--show-ids` puts the device and inode of next to
+    pub fn skip_entry(&mut self, path: &Path, stat: &Stat, reason: SkipReason, options: &DeleteOptions) {
+        self.skip_inner(path, options.display(path, stat), Some(stat), reason);
+    }
+
+    fn skip_inner(&mut self, path: &Path, shown: String, stat: Option<&Stat>, reason: SkipReason) {
+        println!("{} {}, skipping", shown, reason);
+        if let Some(jsonl) = &self.jsonl {
+            jsonl.skipped(path, stat, &reason);
+        }
+        if let Some(partition) = &mut self.partition {
+            partition.skipped(path, &reason);
+        }
+        self.count_skip(reason);
+    }
+
+    /// counts something someone else removed while we were walking, it's only worth
+    /// mentioning with `-v`
+    pub fn vanished(&mut self, path: &Path, options: &DeleteOptions) {
+        if options.verbose {
+            self.skip(path, SkipReason::Vanished);
+        } else {
+            self.count_skip(SkipReason::Vanished);
+        }
+    }
+
+    /// counts something as skipped without saying so
+    pub fn count_skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason.name()).or_default() += 1;
+        match reason {
+            SkipReason::OwnedBy(uid) => *self.skipped_owners.entry(uid).or_default() += 1,
+            SkipReason::InGroup(gid) => *self.skipped_groups.entry(gid).or_default() += 1,
+            SkipReason::OnDevice(dev) => *self.skipped_devices.entry(dev).or_default() += 1,
+            _ => {}
+        }
+    }
+
+    /// counts a file or symlink as deleted
+    pub fn record_deleted(&mut self, path: &Path, stat: &Stat, options: &DeleteOptions) {
+        self.files_deleted += 1;
+        self.bytes_freed += options.freed_size(stat);
+        self.record_removed(path, options);
+        if let Some(jsonl) = &self.jsonl {
+            jsonl.removed(path, stat, options.removal(), options.dryrun);
+        }
+        if let Some(partition) = &mut self.partition {
+            partition.deleted(path);
+        }
+        #[cfg(all(unix, feature = "syslog"))]
+        if let Some(syslog) = self.syslog.as_ref().filter(|_| !options.dryrun) {
+            syslog.removed(path, options.removal());
+        }
+        // the last name of one we've seen has a count of 1 by the time we get to it
+        if options.hardlink_report && stat.is_file() && (stat.nlink() > 1 || self.hardlinks.contains_key(&(stat.dev(), stat.ino()))) {
+            let entry = self.hardlinks.entry((stat.dev(), stat.ino())).or_insert_with(|| Hardlinked {
+                paths: Vec::new(),
+                most_links: 0,
+                fewest_links: u64::MAX,
+                size: options.freed_size(stat),
+            });
+            entry.paths.push(path.to_path_buf());
+            entry.most_links = entry.most_links.max(stat.nlink());
+            entry.fewest_links = entry.fewest_links.min(stat.nlink());
+        }
+        if options.report_largest > 0 {
+            self.largest.push(std::cmp::Reverse((options.freed_size(stat), path.to_path_buf())));
+            if self.largest.len() > options.report_largest {
+                self.largest.pop();
+            }
+        }
+    }
+
+    /// adds a deleted file to what its quota project got back, if we know which one it was in
+    pub fn record_project(&mut self, project: Option<u32>, stat: &Stat, options: &DeleteOptions) {
+        if let Some(project) = project {
+            *self.projects.entry(project).or_default() += options.freed_size(stat);
+        }
+    }
+
+    /// the files `--report-largest` kept track of, biggest first
+    pub fn largest(&self) -> Vec<(u64, &Path)> {
+        let mut largest: Vec<_> = self.largest.iter().map(|std::cmp::Reverse((size, path))| (*size, path.as_path())).collect();
+        largest.sort_by(|a, b| b.cmp(a));
+        largest
+    }
+
+    /// counts a directory as removed
+    pub fn record_dir_removed(&mut self, path: &Path, stat: &Stat, options: &DeleteOptions) {
+        self.dirs_removed += 1;
+        self.record_removed(path, options);
+        if let Some(jsonl) = &self.jsonl {
+            jsonl.removed(path, stat, "remove_dir", options.dryrun);
+        }
+        if let Some(partition) = &mut self.partition {
+            partition.deleted(path);
+        }
+        #[cfg(all(unix, feature = "syslog"))]
+        if let Some(syslog) = self.syslog.as_ref().filter(|_| !options.dryrun) {
+            syslog.removed(path, "remove_dir");
+        }
+    }
+
+    /// remembers that something is gone, if `--two-phase` is going to check
+    pub fn record_removed(&mut self, path: &Path, options: &DeleteOptions) {
+        if options.two_phase && !options.dryrun {
+            self.removed.push(path.to_path_buf());
+        }
+        if let Some(every) = options.sync_every.filter(|_| !options.dryrun) {
+            if let Some(parent) = path.parent() {
+                self.unsynced.insert(parent.to_path_buf());
+            }
+            self.removed_since_sync += 1;
+            if self.removed_since_sync >= every {
+                self.sync();
+            }
+        }
+    }
+
+    /// fsyncs every directory something was removed from since the last time, so the
+    /// removals so far are on disk, for `--sync-every`
+    pub fn sync(&mut self) {
+        self.removed_since_sync = 0;
+        // the deepest go first, so a directory is synced after what was taken out of it
+        for dir in std::mem::take(&mut self.unsynced).into_iter().rev() {
+            match std::fs::File::open(&dir).and_then(|d| d.sync_all()) {
+                Ok(()) => log::debug!("synced {}", dir.display()),
+                // it was removed itself since, and its parent is in the list for that
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    println!("error syncing {}: {}", dir.display(), e);
+                    self.record_error(&e);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn plural(n: usize, word: &str) -> String {
+    if n == 1 { format!("{} {}", n, word) } else { format!("{} {}s", n, word) }
+}
+
+/// counts of what happened to the entries of a single directory, used for `--dir-summary`
+#[derive(Default)]
+struct DirSummary {
+    files: usize,
+    dirs: usize,
+    symlinks: usize,
+    files_skipped: usize,
+    dirs_skipped: usize,
+    symlinks_skipped: usize,
+}
+
+impl DirSummary {
+    fn record(&mut self, stat: &Stat, deleted: bool) {
+        match (stat.is_symlink(), stat.is_dir(), deleted) {
+            (true, _, true) => self.symlinks += 1,
+            (true, _, false) => self.symlinks_skipped += 1,
+            (false, true, true) => self.dirs += 1,
+            (false, true, false) => self.dirs_skipped += 1,
+            (false, false, true) => self.files += 1,
+            (false, false, false) => self.files_skipped += 1,
+        }
+    }
+
+    fn print(&self, path: &Path) {
+        let mut line = format!("cleaned {}: {}, {}", path.display(), plural(self.files, "file"), plural(self.dirs, "dir"));
+        if self.symlinks > 0 {
+            line.push_str(&format!(", {}", plural(self.symlinks, "symlink")));
+        }
+        for (n, word) in [(self.files_skipped, "file"), (self.dirs_skipped, "dir"), (self.symlinks_skipped, "symlink")] {
+            if n > 0 {
+                line.push_str(&format!(", {} skipped", plural(n, word)));
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+/// asks the user a yes/no question on stdin, anything other than y/yes counts as no
+pub fn confirm(question: &str) -> bool {
+    use std::io::Write;
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn is_hidden(name: &OsStr) -> bool {
+    name.as_bytes().starts_with(b".")
+}
+
+/// returns true if something is mounted on an entry, given its stat and its parent's device
+fn is_mountpoint(stat: &Stat, parent_dev: u64) -> bool {
+    stat.dev() != parent_dev
+}
+
+/// the mount points at or under `start`, rewritten to begin with `path` instead of its
+/// canonical form, so they compare equal to the paths the walk builds
+pub fn mount_points_under(path: &Path, start: &Path) -> Option<std::collections::HashSet<PathBuf>> {
+    Some(mount_points()?.into_iter().filter_map(|m| m.strip_prefix(start).ok().map(|rest| path.join(rest))).collect())
+}
+
+/// returns every mount point on the system, or None if we can't tell
+pub fn mount_points() -> Option<Vec<PathBuf>> {
+    let mounts = std::fs::read("/proc/self/mounts").ok()?;
+    let mut points = Vec::new();
+    for line in mounts.split(|&b| b == b'\n') {
+        let field = match line.split(|&b| b == b' ').nth(1) {
+            Some(f) => f,
+            None => continue,
+        };
+        // spaces and friends are escaped as \ooo octal
+        let mut decoded = Vec::with_capacity(field.len());
+        let mut i = 0;
+        while i < field.len() {
+            if field[i] == b'\\' && i + 4 <= field.len() {
+                if let Ok(c) = u8::from_str_radix(std::str::from_utf8(&field[i + 1..i + 4]).unwrap_or(""), 8) {
+                    decoded.push(c);
+                    i += 4;
+                    continue;
+                }
+            }
+            decoded.push(field[i]);
+            i += 1;
+        }
+        points.push(PathBuf::from(std::ffi::OsString::from_vec(decoded)));
+    }
+    Some(points)
+}
+
+/// where the symlink `name` in `dir` leads, as far as it can be resolved. a dangling link
+/// still leads somewhere, so the part that's missing is added on as it's written
+fn link_target(dir: &Dir, name: &OsStr, path: &Path) -> Option<PathBuf> {
+    if let Ok(target) = path.canonicalize() {
+        return Some(target);
+    }
+    let link = dir.read_link(name).ok()?;
+    let parent = path.parent()?.canonicalize().ok()?;
+    let mut target = PathBuf::new();
+    // `..` has to be applied by hand, since there's nothing there to resolve it on
+    for component in parent.join(link).components() {
+        match component {
+            std::path::Component::ParentDir => {
+                target.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => target.push(other),
+        }
+    }
+    Some(target)
+}
+
+/// unmounts a path, if that fails and `lazy` is set then tries again lazily, so something
+/// still using it doesn't stop it going
+pub(crate) fn unmount(path: &Path, lazy: bool) -> std::io::Result<()> {
+    use libc::*;
+    let path_cstr = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    // this will be different on macos and linux
+    #[cfg(target_os = "linux")]
+    {
+        if unsafe { umount2(path_cstr.as_ptr(), 0) } == 0 {
+            return Ok(());
+        }
+        let e = std::io::Error::last_os_error();
+        if lazy && (unsafe { umount2(path_cstr.as_ptr(), MNT_DETACH) } == 0
+            || unsafe { umount2(path_cstr.as_ptr(), MNT_DETACH | MNT_FORCE) } == 0) {
+            return Ok(());
+        }
+        Err(e)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let flags = if lazy { MNT_FORCE } else { 0 };
+        if unsafe { unmount(path_cstr.as_ptr(), flags) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (path_cstr, lazy);
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+}
+
+/// parses a size such as `512`, `10K`, `1.5G` or `2GiB` into bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_lowercase().trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// parses a duration like `90`, `30s`, `10m`, `1.5h` or `2d`, a bare number is in seconds
+pub fn parse_duration(duration: &str) -> Option<std::time::Duration> {
+    let duration = duration.trim();
+    let split = duration.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    std::time::Duration::try_from_secs_f64(number * multiplier).ok()
+}
+
+/// formats a number of bytes for people to read, like `1.5GiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+/// fails on purpose if we're simulating errors and it's this removal's turn
+fn simulate_error(options: &DeleteOptions) -> std::io::Result<()> {
+    #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+    if let Some(simulate_errors) = &options.simulate_errors {
+        return simulate_errors.maybe_fail();
+    }
+    let _ = options;
+    Ok(())
+}
+
+/// removes a file, or moves it to the trash or the `--move-to` directory, backing it up first and
+/// leaving a placeholder after if we're doing those
+pub(crate) fn remove_file(dir: &Dir, name: &OsStr, path: &Path, stat: &Stat, options: &DeleteOptions) -> std::io::Result<()> {
+    simulate_error(options)?;
+    // there's nothing left to read afterwards
+    let hash = match &options.hash_manifest {
+        Some(_) if stat.is_file() => Some(manifest::Manifest::hash(dir, name)?),
+        _ => None,
+    };
+    if let Some(backup) = &options.backup {
+        backup.backup(dir, name, path, stat, &options.starting_dir)?;
+    }
+    if let Some(move_to) = &options.move_to {
+        move_to.move_file(dir, name, path, stat, &options.starting_dir)?;
+    } else if let Some(rename) = &options.rename {
+        let renamed = rename.rename(dir, name, path)?;
+        if options.verbose { println!("renamed {} to {}", path.display(), renamed.display()); }
+    } else if let Some(trash) = &options.trash {
+        if let trash::Trashed::Redundant = trash.trash(dir, name, path, stat)? {
+            if options.verbose { println!("the trash has a copy of {} that's no older, deleted it permanently", path.display()); }
+        }
+    } else {
+        clearing_attrs(dir, name, path, options, || dir.unlink(name))?;
+    }
+    if let Some((manifest, hash)) = options.hash_manifest.as_ref().zip(hash) {
+        manifest.record(path, stat.size(), &hash);
+    }
+    match &options.placeholder {
+        Some(contents) if stat.is_file() => leave_placeholder(dir, name, stat, contents),
+        _ => Ok(()),
+    }
+}
+
+/// hands a file to `--io-uring` to be unlinked, counting it once the kernel says it's gone
+#[cfg_attr(not(all(target_os = "linux", feature = "io-uring")), allow(unused_variables))]
+fn queue_unlink(dir: &Dir, name: &OsStr, path: &Path, stat: &Stat, options: &DeleteOptions, state: &mut RunState) {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if let Some(ring) = &options.io_uring {
+        let queued = ring.borrow_mut().unlink(dir, name, path.to_path_buf(), *stat);
+        match queued {
+            Ok(done) => finish_unlinks(done, options, state),
+            Err(e) => {
+                println!("error deleting {}: {}", path.display(), e);
+                state.record_failed(path, &e);
+            }
+        }
+    }
+}
+
+/// waits for the files queued up for `--io-uring`, which has to happen before the directories
+/// they're in are closed or checked for being empty
+fn flush_unlinks(options: &DeleteOptions, state: &mut RunState) {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if let Some(ring) = &options.io_uring {
+        let done = ring.borrow_mut().flush();
+        finish_unlinks(done, options, state);
+    }
+    let _ = (options, state);
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+fn finish_unlinks(done: Vec<uring::Done>, options: &DeleteOptions, state: &mut RunState) {
+    for uring::Done { path, stat, result } in done {
+        match result {
+            Ok(()) => state.record_deleted(&path, &stat, options),
+            Err(e) => {
+                println!("error deleting {}: {}", path.display(), e);
+                state.record_failed(&path, &e);
+            }
+        }
+    }
+}
+
+/// runs `remove`, and if that wasn't allowed because of `chattr +i` or `+a` on the entry or
+/// the directory it's in, clears them and tries again with `--clear-attrs`
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub(crate) fn clearing_attrs(dir: &Dir, name: &OsStr, path: &Path, options: &DeleteOptions, remove: impl Fn() -> std::io::Result<()>) -> std::io::Result<()> {
+    match remove() {
+        #[cfg(target_os = "linux")]
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) && options.clear_attrs => {
+            if attrs::clear(dir, name, path)? {
+                return remove();
+            }
+            Err(e)
+        }
+        other => other,
+    }
+}
+
+/// puts a file with `contents` where a deleted one was, with the permissions it had
+fn leave_placeholder(dir: &Dir, name: &OsStr, stat: &Stat, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    dir.create_file(name, stat.mode())
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|e| std::io::Error::new(e.kind(), format!("couldn't leave a placeholder: {}", e)))
+}
+
+/// waits for the files being copied into the trash in the background, reporting the ones that
+/// failed. they're still where they were, so they don't count as deleted after all
+pub fn wait_for_copies(options: &DeleteOptions, state: &mut RunState) {
+    let Some(copier) = options.trash.as_ref().and_then(|t| t.copier.as_ref()) else {
+        return;
+    };
+    for failed in copier.wait() {
+        println!("error trashing {}: {}", failed.path.display(), failed.error);
+        state.record_failed(&failed.path, &failed.error);
+        state.files_deleted = state.files_deleted.saturating_sub(1);
+        state.bytes_freed = state.bytes_freed.saturating_sub(options.freed_size(&failed.stat));
+        state.failed_copies.push(failed.path);
+    }
+}
+
+/// lists everything still under a directory, deepest first, without following symlinks
+pub fn leftovers(path: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            let entry_path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                found.extend(leftovers(&entry_path));
+            }
+            found.push(entry_path);
+        }
+    }
+    found
+}
+
+/// why an entry was left alone
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SkipReason {
+    Symlink,
+    AboveStart,
+    Hidden,
+    MountPoint,
+    /// a directory, and we're not deleting recursively
+    Directory,
+    NewerThanStart,
+    /// a directory that still had something in it after we were done with its contents
+    NotEmpty,
+    /// a directory that was swapped for something else between looking at it and opening it
+    Changed,
+    /// a file that doesn't match `--regex`
+    NotMatched,
+    /// a file at the `--include-at-depth` depth that doesn't match its pattern
+    NotMatchedAtDepth(usize),
+    /// a file that matches `--exclude-regex`
+    Excluded,
+    /// a file at the `--exclude-at-depth` depth that matches its pattern
+    ExcludedAtDepth(usize),
+    /// a file whose `--move-to` destination is already taken, with `--on-collision skip`
+    DestinationExists,
+    /// a file whose name is what `--rename` would have made it, so it was renamed before
+    AlreadyRenamed,
+    /// something on a filesystem like proc or sysfs, with its type
+    PseudoFilesystem(&'static str),
+    /// a file whose `--rename` name is taken, with `--on-collision skip`
+    RenameTaken,
+    /// a directory, which stays where it is with `--rename`
+    Renaming,
+    /// a file git doesn't ignore, with `--gitignore`
+    NotIgnored,
+    /// a file git tracks, with `--gitignore`
+    Tracked,
+    /// something closer to the starting path than `--min-depth`
+    AboveMinDepth,
+    /// the starting directory, with `--preserve-top`
+    PreserveTop,
+    /// the newest file in its directory, with `--preserve-newest-in-each-dir`
+    NewestInDir,
+    /// something used more recently than the `--tmpfiles` age
+    TooNew,
+    /// a file used within the `--not-accessed-since` window
+    RecentlyAccessed,
+    /// something that was removed by something else between listing its directory and
+    /// looking at it
+    Vanished,
+    /// anything but a dangling symlink, with `--prune-broken-symlinks`
+    NotDangling,
+    /// a file some process has open, with `--skip-open`
+    OpenBy(u32),
+    /// something owned by another user than `--owner`, with the uid that owns it
+    OwnedBy(u32),
+    /// something in another group than `--group`, with the gid it's in
+    InGroup(u32),
+    /// something taken out of the list in the editor, with `--interactive-editor`
+    Deselected,
+    /// a mount point we stopped at, with `--no-descend-mountpoints`
+    OtherFilesystem,
+    /// a directory someone other than the owner of the starting directory owns, with its uid
+    OtherUsersDir(u32),
+    /// a file someone else owns, with `--only-if-owned-and-writable`, with the uid that owns it
+    NotYours(u32),
+    /// a file in a directory we can't write to, with `--only-if-owned-and-writable`
+    ParentNotWritable,
+    /// something the `--path-filter` program didn't agree to
+    Rejected,
+    /// a directory the user said no to, with `--confirm-each-dir-with-preview`
+    Declined,
+    /// something that isn't what it was when the `--plan-file` was made
+    ChangedSincePlan,
+    /// a device node, socket or fifo on another device than the starting path, with
+    /// `--skip-device-files-on-other-fs`
+    DeviceOnOtherFs { dev: u64, kind: &'static str },
+    /// something on a filesystem `--device-whitelist` or `--device-blacklist` rules out, with
+    /// its device
+    OnDevice(u64),
+    /// an emptied directory listed in the `--keep-structure-file`
+    KeptStructure,
+}
+
+impl SkipReason {
+    /// a short name for the reason, for reports and counting
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkipReason::Symlink => "symlink",
+            SkipReason::AboveStart => "above_start",
+            SkipReason::Hidden => "hidden",
+            SkipReason::MountPoint => "mount_point",
+            SkipReason::Directory => "directory",
+            SkipReason::NewerThanStart => "newer_than_start",
+            SkipReason::NotEmpty => "not_empty",
+            SkipReason::Changed => "changed",
+            SkipReason::NotMatched => "not_matched",
+            SkipReason::Excluded => "excluded",
+            SkipReason::NotMatchedAtDepth(_) => "not_matched_at_depth",
+            SkipReason::ExcludedAtDepth(_) => "excluded_at_depth",
+            SkipReason::DestinationExists => "destination_exists",
+            SkipReason::AlreadyRenamed => "already_renamed",
+            SkipReason::PseudoFilesystem(_) => "pseudo_filesystem",
+            SkipReason::RenameTaken => "rename_taken",
+            SkipReason::Renaming => "renaming",
+            SkipReason::NotIgnored => "not_ignored",
+            SkipReason::Tracked => "tracked",
+            SkipReason::AboveMinDepth => "above_min_depth",
+            SkipReason::PreserveTop => "preserve_top",
+            SkipReason::NewestInDir => "newest_in_dir",
+            SkipReason::TooNew => "too_new",
+            SkipReason::RecentlyAccessed => "recently_accessed",
+            SkipReason::Vanished => "vanished",
+            SkipReason::NotDangling => "not_dangling",
+            SkipReason::OpenBy(_) => "open",
+            SkipReason::OwnedBy(_) => "owner",
+            SkipReason::InGroup(_) => "group",
+            SkipReason::OtherUsersDir(_) => "other_users_dir",
+            SkipReason::NotYours(_) => "not_yours",
+            SkipReason::ParentNotWritable => "parent_not_writable",
+            SkipReason::Deselected => "deselected",
+            SkipReason::OtherFilesystem => "other_filesystem",
+            SkipReason::Rejected => "rejected",
+            SkipReason::Declined => "declined",
+            SkipReason::ChangedSincePlan => "changed_since_plan",
+            SkipReason::DeviceOnOtherFs { .. } => "device_on_other_fs",
+            SkipReason::OnDevice(_) => "device_ruled_out",
+            SkipReason::KeptStructure => "kept_structure",
+        }
+    }
+}
+
+/// what gets printed after the path, as in `<path> is a hidden file, skipping`
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reason = match self {
+            SkipReason::Symlink => "is a symlink",
+            SkipReason::AboveStart => "is above starting dir",
+            SkipReason::Hidden => "is a hidden file",
+            SkipReason::MountPoint => "is a mount point",
+            SkipReason::Directory => "is a directory",
+            SkipReason::NewerThanStart => "created after run start",
+            SkipReason::NotEmpty => "is not empty after recursion",
+            SkipReason::Changed => "changed while we were looking at it",
+            SkipReason::NotMatched => "doesn't match --regex",
+            SkipReason::Excluded => "matches --exclude-regex",
+            SkipReason::DestinationExists => "already exists in the --move-to directory",
+            SkipReason::AlreadyRenamed => "looks like it was renamed by --rename already",
+            SkipReason::RenameTaken => "would be renamed to something that already exists",
+            SkipReason::Renaming => "is a directory, and only files are renamed",
+            SkipReason::ParentNotWritable => "is in a directory you can't write to, so it can't be removed",
+            SkipReason::NotIgnored => "isn't ignored by git",
+            SkipReason::Tracked => "is tracked by git",
+            SkipReason::AboveMinDepth => "is above --min-depth",
+            SkipReason::PreserveTop => "is the starting directory and --preserve-top is set",
+            SkipReason::NewestInDir => "is the newest file in its directory",
+            SkipReason::TooNew => "was used more recently than the --tmpfiles age",
+            SkipReason::RecentlyAccessed => "was used within the --not-accessed-since window",
+            SkipReason::Vanished => "was removed by something else while we were walking",
+            SkipReason::NotDangling => "isn't a dangling symlink",
+            SkipReason::Deselected => "was taken out of the list",
+            SkipReason::OtherFilesystem => "has another filesystem mounted on it",
+            SkipReason::Rejected => "was rejected by --path-filter",
+            SkipReason::Declined => "wasn't confirmed",
+            SkipReason::ChangedSincePlan => "changed since the plan was made",
+            SkipReason::KeptStructure => "is empty now and kept, it's in the --keep-structure-file",
+            SkipReason::NotMatchedAtDepth(depth) => return write!(f, "doesn't match --include-at-depth at depth {}", depth),
+            SkipReason::ExcludedAtDepth(depth) => return write!(f, "matches --exclude-at-depth at depth {}", depth),
+            SkipReason::OpenBy(pid) => return write!(f, "is open by pid {}", pid),
+            SkipReason::OwnedBy(uid) => return write!(f, "is owned by {}", owner::user_name(*uid)),
+            SkipReason::InGroup(gid) => return write!(f, "belongs to group {}", owner::group_name(*gid)),
+            SkipReason::OtherUsersDir(uid) => {
+                return write!(f, "is owned by uid {} ({}), not the owner of the starting directory", uid, owner::user_name(*uid))
+            }
+            SkipReason::NotYours(uid) => return write!(f, "is owned by {}, not you", owner::user_name(*uid)),
+            SkipReason::PseudoFilesystem(kind) => return write!(f, "is on a {} filesystem, which saferm never deletes from", kind),
+            SkipReason::OnDevice(dev) => return write!(f, "is on device {}, which this run leaves alone", devices::format_dev(*dev)),
+            SkipReason::DeviceOnOtherFs { dev, kind } => {
+                return write!(f, "is a {} on device {}, another filesystem than the starting path", kind, dev)
+            }
+        };
+        f.write_str(reason)
+    }
+}
+
+/// what the checks decided should happen to an entry
+#[derive(Debug)]
+pub enum Decision {
+    Skip(SkipReason),
+    RemoveSymlink,
+    /// remove a symlink that points to something that doesn't exist
+    PruneSymlink,
+    Unmount,
+    /// go through a directory's contents, with the stat of the directory itself
+    /// (what the symlink points to, if we're entering one)
+    Recurse(Stat),
+    /// delete a file, with the stat of the file itself
+    Delete(Stat),
+}
+
+/// a single entry `name` in `dir` that we're deciding about
+#[derive(Copy, Clone)]
+pub struct Entry<'a> {
+    pub dir: &'a Dir,
+    pub name: &'a OsStr,
+    /// only used for the checks that need a path, and for printing
+    pub path: &'a Path,
+    /// the one `fstatat` we do for every entry, everything works off of it rather than
+    /// asking the filesystem again
+    pub stat: &'a Stat,
+    /// the device of `dir`
+    pub parent_dev: u64,
+    /// how far below the starting path this is, the starting path itself is 0
+    pub depth: usize,
+}
+
+/// runs every check on an entry and decides what should happen to it, without doing anything
+pub fn decide(entry: &Entry, options: &DeleteOptions) -> std::io::Result<Decision> {
+    let mut trace = Trace(options.trace_decisions.then(Vec::new));
+    let decision = run_checks(entry, options, &mut trace);
+    match &decision {
+        Ok(decision) => log::trace!("{}: {:?}", entry.path.display(), decision),
+        Err(e) => log::trace!("{}: couldn't decide: {}", entry.path.display(), e),
+    }
+    if let Some(mut steps) = trace.0 {
+        let (last, verdict) = match &decision {
+            Ok(Decision::Skip(reason)) => (Some(reason.to_string()), "SKIP"),
+            Ok(Decision::RemoveSymlink) => (Some("removing symlinks".to_string()), "REMOVE SYMLINK"),
+            Ok(Decision::PruneSymlink) => (Some("dangling".to_string()), "PRUNE"),
+            Ok(Decision::Unmount) => (Some("is a mount point".to_string()), "UNMOUNT"),
+            Ok(Decision::Recurse(_)) => (Some("is a directory".to_string()), "RECURSE"),
+            Ok(Decision::Delete(_)) => (None, "DELETE"),
+            Err(e) => (Some(e.to_string()), "ERROR"),
+        };
+        steps.extend(last);
+        println!("{}: {} -> {}", entry.path.display(), steps.join("; "), verdict);
+    }
+    decision
+}
+
+/// the checks an entry got through, kept only with `--trace-decisions`
+struct Trace(Option<Vec<String>>);
+
+impl Trace {
+    fn passed(&mut self, step: impl FnOnce() -> String) {
+        if let Some(steps) = &mut self.0 {
+            steps.push(step());
+        }
+    }
+}
+
+/// what keeps a symlink we'd otherwise remove, whether it's dangling or not
+fn symlink_checks(entry: &Entry, options: &DeleteOptions, trace: &mut Trace) -> Option<SkipReason> {
+    let Entry { name, path, stat, depth, .. } = *entry;
+    if let Some(reason) = options.filters.check(name, path, depth) {
+        return Some(reason);
+    }
+    if !options.filters.is_empty() {
+        trace.passed(|| "passes filters".into());
+    }
+    if depth < options.min_depth {
+        return Some(SkipReason::AboveMinDepth);
+    }
+    if options.age.as_ref().is_some_and(|age| !age.is_old(stat)) {
+        return Some(SkipReason::TooNew);
+    }
+    if let Some(reason) = options.check_ownership(stat) {
+        return Some(reason);
+    }
+    if options.deselected(path) {
+        return Some(SkipReason::Deselected);
+    }
+    None
+}
+
+/// the checks behind `decide`, in the order they happen
+fn run_checks(entry: &Entry, options: &DeleteOptions, trace: &mut Trace) -> std::io::Result<Decision> {
+    let Entry { dir, name, path, stat, parent_dev, depth } = *entry;
+    if let Some(kind) = options.pseudo_filesystems.get(&stat.dev()) {
+        return Ok(Decision::Skip(SkipReason::PseudoFilesystem(kind)));
+    }
+    if !stat.is_dir() && options.rename.as_ref().is_some_and(|r| r.is_renamed(name)) {
+        return Ok(Decision::Skip(SkipReason::AlreadyRenamed));
+    }
+    // check if path is a symlink
+    if stat.is_symlink() {
+        // dangling symlinks go no matter what we'd do with other symlinks
+        if options.prune_broken_symlinks {
+            if let Err(e) = dir.stat_child_follow(name) {
+                if e.raw_os_error() == Some(libc::ENOENT) {
+                    trace.passed(|| "is a dangling symlink".into());
+                    if let Some(reason) = symlink_checks(entry, options, trace) {
+                        return Ok(Decision::Skip(reason));
+                    }
+                    return Ok(Decision::PruneSymlink);
+                }
+            }
+        }
+        // if we are supposed to remove symlinks, remove it
+        if options.remove_symlinks {
+            if options.prune_broken_symlinks {
+                return Ok(Decision::Skip(SkipReason::NotDangling));
+            }
+            trace.passed(|| "is a symlink".into());
+            if let Some(reason) = symlink_checks(entry, options, trace) {
+                return Ok(Decision::Skip(reason));
+            }
+            return Ok(Decision::RemoveSymlink);
+        }
+        if !options.enter_symlinks {
+            return Ok(Decision::Skip(SkipReason::Symlink));
+        }
+        trace.passed(|| "is a symlink, entering it".into());
+    } else {
+        trace.passed(|| "not a symlink".into());
+    }
+    // we're entering a symlink, so from here on go by what it points to
+    let followed;
+    let stat = if stat.is_symlink() {
+        followed = dir.stat_child_follow(name)?;
+        &followed
+    } else {
+        stat
+    };
+    // a symlink into /proc is no more a reason to go there than being in it
+    if let Some(kind) = options.pseudo_filesystems.get(&stat.dev()) {
+        return Ok(Decision::Skip(SkipReason::PseudoFilesystem(kind)));
+    }
+    trace.passed(|| "not on a pseudo filesystem".into());
+    // check if path is above starting dir
+    if !options.allow_delete_above_start {
+        match path.canonicalize() {
+            Ok(canonical) if !canonical.starts_with(&options.starting_dir) => return Ok(Decision::Skip(SkipReason::AboveStart)),
+            Ok(_) => {}
+            // it's gone since we listed the directory, so there's nothing left to do with it
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !options.strict_canonicalize => {
+                return Ok(Decision::Skip(SkipReason::Vanished));
+            }
+            Err(e) => return Err(e),
+        }
+        trace.passed(|| "within start".into());
+    }
+    // check if this is a hidden file or directory, asking about it is up to whoever acts on this
+    if is_hidden(name) {
+        if options.dotfiles == Dotfiles::Skip {
+            return Ok(Decision::Skip(SkipReason::Hidden));
+        }
+        trace.passed(|| "hidden but allowed".into());
+    } else {
+        trace.passed(|| "not hidden".into());
+    }
+    // device nodes somewhere else are most likely a bind mounted /dev or similar. this goes
+    // before the mount point check so they're reported as what they are
+    if let (Some(start_dev), Some(kind)) = (options.device_files_dev, stat.special_kind()) {
+        if stat.dev() != start_dev {
+            return Ok(Decision::Skip(SkipReason::DeviceOnOtherFs { dev: stat.dev(), kind }));
+        }
+    }
+    // a directory on a filesystem we can't touch is still gone into, in case something it
+    // holds is mounted from one we can, unless it was ruled out by name
+    if let Some(devices) = &options.devices {
+        let dev = stat.dev();
+        if !devices.allows(dev) && (devices.denied.contains(&dev) || !stat.is_dir() || options.is_mounted(path, stat, parent_dev)) {
+            return Ok(Decision::Skip(SkipReason::OnDevice(dev)));
+        }
+        trace.passed(|| format!("on device {}, which can be touched", devices::format_dev(dev)));
+    }
+    let crosses = options.devices.as_ref().is_some_and(|d| d.crosses_into(stat.dev()));
+    // check if path is a mount point. everything below the start is compared to the directory
+    // holding it, so only the start itself can be let through, along with whitelisted devices
+    if options.is_mounted(path, stat, parent_dev) && !(options.no_descend_mountpoints && depth == 0) && !crosses {
+        if options.mountpoints == Mountpoints::Unmount {
+            return Ok(Decision::Unmount);
+        }
+        if options.no_descend_mountpoints {
+            return Ok(Decision::Skip(SkipReason::OtherFilesystem));
+        }
+        return Ok(Decision::Skip(SkipReason::MountPoint));
+    }
+    trace.passed(|| "not a mount point".into());
+    // check if path is a directory
+    if stat.is_dir() {
+        if options.recursive {
+            if options.dir_owner.is_some_and(|uid| stat.uid() != uid) {
+                return Ok(Decision::Skip(SkipReason::OtherUsersDir(stat.uid())));
+            }
+            return Ok(Decision::Recurse(*stat));
+        }
+        return Ok(Decision::Skip(SkipReason::Directory));
+    }
+    trace.passed(|| match stat.special_kind() {
+        Some(kind) => format!("is a {}", kind),
+        None => format!("is a regular file of {}", format_size(stat.size())),
+    });
+    // check if the file was created after we started
+    if options.exclude_newer_than_start {
+        if stat.modified() > options.start_time {
+            return Ok(Decision::Skip(SkipReason::NewerThanStart));
+        }
+        trace.passed(|| "older than the run".into());
+    }
+    if let Some(age) = &options.age {
+        if !age.is_old(stat) {
+            return Ok(Decision::Skip(SkipReason::TooNew));
+        }
+        trace.passed(|| "older than the --tmpfiles age".into());
+    }
+    if let Some(age) = &options.not_accessed_since {
+        if !age.is_old(stat) {
+            return Ok(Decision::Skip(SkipReason::RecentlyAccessed));
+        }
+        trace.passed(|| "not used within --not-accessed-since".into());
+    }
+    if depth < options.min_depth {
+        return Ok(Decision::Skip(SkipReason::AboveMinDepth));
+    }
+    if options.prune_broken_symlinks {
+        return Ok(Decision::Skip(SkipReason::NotDangling));
+    }
+    if let Some(reason) = options.filters.check(name, path, depth) {
+        return Ok(Decision::Skip(reason));
+    }
+    if !options.filters.is_empty() {
+        trace.passed(|| "passes filters".into());
+    }
+    if let Some(reason) = options.check_ownership(stat) {
+        return Ok(Decision::Skip(reason));
+    }
+    if options.owner.is_some() || options.group.is_some() {
+        trace.passed(|| "owner and group match".into());
+    }
+    if let Some(uid) = options.owned_and_writable {
+        if stat.uid() != uid {
+            return Ok(Decision::Skip(SkipReason::NotYours(stat.uid())));
+        }
+        if !dir.writable() {
+            return Ok(Decision::Skip(SkipReason::ParentNotWritable));
+        }
+        trace.passed(|| "yours, in a directory you can write to".into());
+    }
+    // this is the slowest check by far, so it goes after everything that's cheap
+    if let Some(filter) = &options.path_filter {
+        if !filter.allows(path) {
+            return Ok(Decision::Skip(SkipReason::Rejected));
+        }
+        trace.passed(|| "allowed by --path-filter".into());
+    }
+    if let Some(pid) = options.open_files.as_ref().and_then(|o| o.holder(stat)) {
+        return Ok(Decision::Skip(SkipReason::OpenBy(pid)));
+    }
+    if options.move_to.as_ref().is_some_and(|m| m.collides(name, path, &options.starting_dir)) {
+        return Ok(Decision::Skip(SkipReason::DestinationExists));
+    }
+    if options.rename.as_ref().is_some_and(|r| r.collides(dir, name)) {
+        return Ok(Decision::Skip(SkipReason::RenameTaken));
+    }
+    if options.deselected(path) {
+        return Ok(Decision::Skip(SkipReason::Deselected));
+    }
+    Ok(Decision::Delete(*stat))
+}
+
+/// where a run starts: the directory holding the starting path, its name in there, its stat
+/// and the device of the directory holding it
+pub(crate) struct Start<'a> {
+    pub dir: Dir,
+    pub name: &'a OsStr,
+    pub stat: Stat,
+    pub parent_dev: u64,
+}
+
+impl<'a> Start<'a> {
+    /// opens the parent of `path`, returns `None` for the root directory since it has no parent
+    pub fn open(path: &'a Path) -> std::io::Result<Option<Start<'a>>> {
+        let parent = match path.parent() {
+            // a relative path with only one component is in the working directory
+            Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+            Some(parent) => parent,
+            None => return Ok(None),
+        };
+        let name = path.file_name()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path doesn't name a file"))?;
+        let dir = Dir::open(parent)?;
+        let parent_dev = dir.stat()?.dev();
+        let stat = dir.stat_child(name)?;
+        Ok(Some(Start { dir, name, stat, parent_dev }))
+    }
+}
+
+/// deletes a path, this is where a run starts. anything that goes wrong is printed and
+/// recorded in `state`
+pub fn delete(path: &str, options: &DeleteOptions, state: &mut RunState) {
+    let path = Path::new(path);
+    log::info!("starting at {}", path.display());
+    match Start::open(path) {
+        Ok(Some(start)) => {
+            let entry = Entry { dir: &start.dir, name: start.name, path, stat: &start.stat, parent_dev: start.parent_dev, depth: 0 };
+            let _ = delete_entry(&entry, options, state);
+            flush_unlinks(options, state);
+        }
+        // if there isn't a parent, this is the root directory, and it's a mountpoint
+        Ok(None) => state.skip(path, SkipReason::MountPoint),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {
+            println!("error: {} doesn't name a file", path.display());
+        }
+        Err(e) => {
+            println!("error reading {}: {}", path.display(), e);
+            state.record_failed(path, &e);
+        }
+    }
+}
+
+/// decides what to do with a single entry and does it
+fn delete_entry(entry: &Entry, options: &DeleteOptions, state: &mut RunState) -> Result<(), ()> {
+    let Entry { dir, name, path, stat, .. } = *entry;
+    if state.stopped {
+        return Err(());
+    }
+    // whatever was already going is done by now, so this is a clean place to stop
+    if options.deadline.is_some_and(|d| options.start_time.elapsed().unwrap_or_default() >= d) {
+        println!("ran out of time for --deadline, stopping before {}", path.display());
+        state.stopped = true;
+        return Err(());
+    }
+    if let Some(checkpoint) = &options.checkpoint {
+        checkpoint.tick();
+    }
+    // the starting path being a symlink is what the user asked for, it's only ones below it
+    if let Some(start) = options.abort_on_symlink_escape.as_ref().filter(|_| stat.is_symlink() && entry.depth > 0) {
+        if let Some(target) = link_target(dir, name, path).filter(|target| !target.starts_with(start)) {
+            println!("error: {} points to {}, outside of {}, aborting", path.display(), target.display(), start.display());
+            state.stopped = true;
+            state.escaped = true;
+            return Err(());
+        }
+    }
+    let decision = match decide(entry, options) {
+        Ok(decision) => decision,
+        Err(e) => {
+            println!("error reading {}: {}", path.display(), e);
+            state.record_failed(path, &e);
+            return Err(());
+        }
+    };
+    if options.dotfiles == Dotfiles::Prompt && is_hidden(name) && !matches!(decision, Decision::Skip(_))
+        && !confirm(&format!("{} is hidden, delete it?", path.display())) {
+        state.skip_entry(path, stat, SkipReason::Hidden, options);
+        return Err(());
+    }
+    match decision {
+        Decision::Skip(SkipReason::MountPoint) if options.mountpoints == Mountpoints::Fail => {
+            println!("error: {} is a mount point, stopping", path.display());
+            state.stopped = true;
+            state.aborted = true;
+            Err(())
+        }
+        // a cache has far more hot files than anyone wants to read about
+        Decision::Skip(SkipReason::RecentlyAccessed) if !options.verbose => {
+            state.count_skip(SkipReason::RecentlyAccessed);
+            Err(())
+        }
+        // it's gone, which is what we were after anyway
+        Decision::Skip(SkipReason::Vanished) => {
+            state.vanished(path, options);
+            Ok(())
+        }
+        Decision::Skip(reason) => {
+            state.skip_entry(path, stat, reason, options);
+            Err(())
+        }
+        Decision::RemoveSymlink | Decision::PruneSymlink => {
+            if matches!(decision, Decision::PruneSymlink) {
+                println!("removing dangling symlink {}", options.display(path, stat));
+            } else {
+                println!("removing symlink {}", options.display(path, stat));
+            }
+            // once the link is gone there's no telling where it went
+            if options.with_targets && matches!(decision, Decision::RemoveSymlink) {
+                if let Ok(target) = path.canonicalize() {
+                    state.link_targets.push((path.to_path_buf(), target));
+                }
+            }
+            if !options.dryrun {
+                if let Err(e) = remove_file(dir, name, path, stat, options) {
+                    println!("error deleting {}: {}", path.display(), e);
+                    state.record_failed(path, &e);
+                    return Err(());
+                }
+            } else {
+                println!("(dryrun) did nothing");
+            }
+            state.record_deleted(path, stat, options);
+            Ok(())
+        }
+        Decision::Unmount => {
+            println!("{} is a mount point, unmounting", options.display(path, stat));
+            if options.dryrun {
+                println!("(dryrun) did nothing");
+                return Ok(());
+            }
+            if let Err(e) = unmount(path, options.unmount_failure.is_none()) {
+                println!("error unmounting {}: {}", path.display(), e);
+                state.record_failed(path, &e);
+                if options.unmount_failure != Some(UnmountFailure::Skip) {
+                    println!("stopping");
+                    state.stopped = true;
+                    state.aborted = true;
+                }
+                return Err(());
+            }
+            Ok(())
+        }
+        Decision::Recurse(dir_stat) => delete_dir(entry, &dir_stat, options, state),
+        Decision::Delete(file_stat) => {
+            // we're only deleting until there's enough space or only duplicates, so hold it back
+            // to do afterwards
+            // the file is gone afterwards, so ask which project it's in first
+            let project = if options.quota_aware { quota::project_of(dir, name, &file_stat) } else { None };
+            if options.hold_back() {
+                state.candidates.push(space::Candidate { path: path.to_path_buf(), stat: file_stat, project });
+                return Ok(());
+            }
+            // delete path
+            if options.verbose { println!("deleting {}", options.display(path, &file_stat)); }
+            if options.verbose && file_stat.allocated() < file_stat.size() {
+                println!("{} is sparse, {} of data in {} on disk", path.display(), format_size(file_stat.size()), format_size(file_stat.allocated()));
+            }
+            if options.batching_unlinks() {
+                queue_unlink(dir, name, path, &file_stat, options, state);
+                return Ok(());
+            }
+            if !options.dryrun {
+                if let Err(e) = remove_file(dir, name, path, &file_stat, options) {
+                    println!("error deleting {}: {}", path.display(), e);
+                    state.record_failed(path, &e);
+                    return Err(());
+                }
+            } else if options.verbose { println!("(dryrun) did nothing"); }
+            state.record_deleted(path, &file_stat, options);
+            state.record_project(project, &file_stat, options);
+            Ok(())
+        }
+    }
+}
+
+/// deletes the contents of a directory and then the directory itself if it ended up empty.
+/// `stat` is the directory's own, which is what a symlink points to if we're entering one
+fn delete_dir(entry: &Entry, stat: &Stat, options: &DeleteOptions, state: &mut RunState) -> Result<(), ()> {
+    let Entry { dir, name, path, depth, .. } = *entry;
+    let is_symlink = entry.stat.is_symlink();
+    // nothing in here needs a decision, so let the standard library do it all at once
+    if !is_symlink && options.can_fast_path(path, depth) {
+        log::debug!("nothing under {} needs checking, removing it all at once", path.display());
+        if let Err(e) = std::fs::remove_dir_all(path) {
+            println!("error deleting {}: {}", path.display(), e);
+            state.record_failed(path, &e);
+            return Err(());
+        }
+        state.record_removed(path, options);
+        return Ok(());
+    }
+    match options.walk_order {
+        Some(WalkOrder::Preorder) => {
+            println!("{} is a directory, recursing", options.display(path, stat));
+            if let Some(jsonl) = &state.jsonl {
+                jsonl.entered(path);
+            }
+        }
+        None if options.verbose => println!("{} is a directory, recursing", options.display(path, stat)),
+        _ => {}
+    }
+    let mut summary = DirSummary::default();
+    let (child, names) = match open_dir(dir, name, stat, is_symlink) {
+        Ok(Some(opened)) => opened,
+        Ok(None) => {
+            state.skip_entry(path, stat, SkipReason::Changed, options);
+            return Err(());
+        }
+        Err(e) => {
+            println!("error reading {}: {}", path.display(), e);
+            state.record_failed(path, &e);
+            return Err(());
+        }
+    };
+    if options.confirm_each_dir && !state.confirmed_all_dirs {
+        preview_dir(&child, path, &names);
+        match ask(&format!("delete contents of {}? [y/N/a/q] ", path.display())) {
+            'y' => {}
+            'a' => state.confirmed_all_dirs = true,
+            'q' => {
+                println!("aborting");
+                state.stopped = true;
+                state.aborted = true;
+                return Err(());
+            }
+            _ => {
+                state.skip_entry(path, stat, SkipReason::Declined, options);
+                return Err(());
+            }
+        }
+    }
+    // the entries of the starting directory are what the checkpoint keeps track of
+    let checkpoint = options.checkpoint.as_ref().filter(|_| path == options.starting_dir);
+    let newest = options.preserve_newest.then(|| newest_file(&child, path, &names, stat.dev(), depth + 1, options)).flatten();
+    let mut prefetched = options.parallel_stat.then(|| prefetch::stat_all(&child, &names, prefetch::JOBS).into_iter());
+    for entry_name in names {
+        if state.stopped {
+            break;
+        }
+        let prefetched_stat = prefetched.as_mut().and_then(Iterator::next);
+        let entry_path = path.join(&entry_name);
+        if checkpoint.is_some_and(|c| c.is_done(&entry_name)) {
+            log::info!("{} was finished in an earlier run", entry_path.display());
+            continue;
+        }
+        let entry_stat = match prefetched_stat.unwrap_or_else(|| child.stat_child(&entry_name)) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !options.strict_canonicalize => {
+                state.vanished(&entry_path, options);
+                continue;
+            }
+            Err(e) => {
+                println!("error reading {}: {}", entry_path.display(), e);
+                state.record_failed(&entry_path, &e);
+                continue;
+            }
+        };
+        if newest.as_ref() == Some(&entry_name) {
+            state.skip_entry(&entry_path, &entry_stat, SkipReason::NewestInDir, options);
+            summary.record(&entry_stat, false);
+            continue;
+        }
+        let errors = state.errors;
+        let child_entry = Entry { dir: &child, name: &entry_name, path: &entry_path, stat: &entry_stat, parent_dev: stat.dev(), depth: depth + 1 };
+        let result = delete_entry(&child_entry, options, state);
+        summary.record(&entry_stat, result.is_ok());
+        // anything that went wrong might go right next time, so only skip it if nothing did
+        if let Some(checkpoint) = checkpoint.filter(|_| state.errors == errors && !state.stopped) {
+            checkpoint.finish(&entry_name);
+        }
+    }
+    flush_unlinks(options, state);
+    if options.dir_summary {
+        // plain lines would end up among the records, so it's one of those instead
+        match &state.jsonl {
+            Some(jsonl) => jsonl.dir_summary(path, &summary),
+            None => summary.print(path),
+        }
+    }
+    if state.stopped {
+        return Err(());
+    }
+    if depth < options.min_depth {
+        state.skip_entry(path, stat, SkipReason::AboveMinDepth, options);
+        return Err(());
+    }
+    if depth == 0 && options.preserve_top {
+        state.skip_entry(path, stat, SkipReason::PreserveTop, options);
+        return Err(());
+    }
+    if options.age.as_ref().is_some_and(|age| !age.is_old_dir(stat)) {
+        state.skip_entry(path, stat, SkipReason::TooNew, options);
+        return Err(());
+    }
+    // we were let into a mount point to clean it, the directory itself can't go
+    if depth == 0 && options.is_mounted(path, stat, entry.parent_dev) {
+        state.skip_entry(path, stat, SkipReason::MountPoint, options);
+        return Err(());
+    }
+    // we still went in to find their files, but someone else's directory stays
+    if let Some(reason) = options.check_ownership(stat) {
+        state.skip_entry(path, stat, reason, options);
+        return Err(());
+    }
+    if let Some(reason) = options.check_device(path, stat, entry.parent_dev) {
+        state.skip_entry(path, stat, reason, options);
+        return Err(());
+    }
+    if options.deselected(path) {
+        state.skip_entry(path, stat, SkipReason::Deselected, options);
+        return Err(());
+    }
+    // the files in it were renamed, not taken out of it
+    if options.rename.is_some() {
+        if options.verbose {
+            state.skip_entry(path, stat, SkipReason::Renaming, options);
+        } else {
+            state.count_skip(SkipReason::Renaming);
+        }
+        return Err(());
+    }
+    // anything still being copied into the trash is still in here
+    wait_for_copies(options, state);
+    // if directory is empty, remove it
+    if child.entries().map(|e| e.is_empty()).unwrap_or(false) {
+        if options.keeps_structure(path) {
+            state.skip_entry(path, stat, SkipReason::KeptStructure, options);
+            return Err(());
+        }
+        println!("removing directory {}", options.display(path, stat));
+        if !options.dryrun {
+            // a symlink we entered is removed as the link, what it pointed to is now empty
+            let removed = simulate_error(options)
+                .and_then(|_| clearing_attrs(dir, name, path, options, || if is_symlink { dir.unlink(name) } else { dir.remove_dir(name) }));
+            if let Err(e) = removed {
+                println!("error deleting {}: {}", path.display(), e);
+                state.record_failed(path, &e);
+                return Err(());
+            }
+        } else {
+            println!("(dryrun) did nothing");
+        }
+        state.record_dir_removed(path, stat, options);
+        Ok(())
+    } else {
+        state.skip_entry(path, stat, SkipReason::NotEmpty, options);
+        Err(())
+    }
+}
+
+/// the name of the newest regular file in `dir` that would otherwise be deleted, for
+/// `--preserve-newest-in-each-dir`. the checks are run on each file to find out, but quietly,
+/// since they're run on it again when it's its turn
+pub(crate) fn newest_file(dir: &Dir, path: &Path, names: &[std::ffi::OsString], dev: u64, depth: usize, options: &DeleteOptions) -> Option<std::ffi::OsString> {
+    let mut newest = None;
+    for name in names {
+        let Ok(stat) = dir.stat_child(name) else { continue };
+        if !stat.is_file() || newest.as_ref().is_some_and(|(modified, _)| *modified >= stat.modified()) {
+            continue;
+        }
+        let entry = Entry { dir, name, path: &path.join(name), stat: &stat, parent_dev: dev, depth };
+        if let Ok(Decision::Delete(_)) = run_checks(&entry, options, &mut Trace(None)) {
+            newest = Some((stat.modified(), name.clone()));
+        }
+    }
+    newest.map(|(_, name)| name)
+}
+
+/// how many entries `--confirm-each-dir-with-preview` shows before asking
+const PREVIEW_SHOWN: usize = 5;
+
+/// shows the first few entries of a directory and how much is directly in it, without
+/// going any further down
+fn preview_dir(dir: &Dir, path: &Path, names: &[std::ffi::OsString]) {
+    let mut size = 0;
+    let mut subdirs = 0;
+    for name in names {
+        match dir.stat_child(name) {
+            Ok(stat) if stat.is_dir() => subdirs += 1,
+            Ok(stat) => size += stat.size(),
+            Err(_) => {}
+        }
+    }
+    println!("{} has {} ({}) and {} of files directly in it:", path.display(), plural(names.len(), "item"), plural(subdirs, "dir"), format_size(size));
+    for name in names.iter().take(PREVIEW_SHOWN) {
+        println!("  {}", name.to_string_lossy());
+    }
+    if names.len() > PREVIEW_SHOWN {
+        println!("  and {} more", names.len() - PREVIEW_SHOWN);
+    }
+}
+
+/// asks a question on stdin and returns the first letter of the answer, lowercased
+fn ask(question: &str) -> char {
+    use std::io::Write;
+    print!("{}", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return 'n';
+    }
+    answer.trim().chars().next().unwrap_or('n').to_ascii_lowercase()
+}
+
+/// opens the directory `name` in `dir` and lists it, making sure what we opened is what we
+/// looked at (`stat`) and not something swapped in since. returns `None` if it was swapped
+pub(crate) fn open_dir(dir: &Dir, name: &OsStr, stat: &Stat, follow: bool) -> std::io::Result<Option<(Dir, Vec<std::ffi::OsString>)>> {
+    let child = dir.open_child(name, follow)?;
+    let child_stat = child.stat()?;
+    if child_stat.dev() != stat.dev() || child_stat.ino() != stat.ino() {
+        log::debug!("expected {:?} but opened {:?}", stat, child_stat);
+        return Ok(None);
+    }
+    let names = child.entries()?;
+    Ok(Some((child, names)))
+}
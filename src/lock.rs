@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// advisory locks that keep two runs from working on overlapping trees at the same time.
+/// a run holds an exclusive lock for the path it works on and shared locks for every parent
+/// of it, so a run on a parent or a child of a locked path can't get its locks, but runs on
+/// unrelated paths (or siblings) can. the lock files are shared by every user, so a run as
+/// root and one as someone else keep out of each other's way too. they're let go of, and
+/// removed if nobody else is using them, when this is dropped
+pub struct Lock {
+    files: Vec<(PathBuf, File)>,
+}
+
+/// where the lock files live, one per path that's been locked. it's world writable and
+/// sticky, like /tmp, so anyone can lock but nobody can remove someone else's files
+fn lock_dir() -> io::Result<PathBuf> {
+    let base = Path::new("/run/lock");
+    let dir = if base.is_dir() { base.join("saferm") } else { std::env::temp_dir().join("saferm-locks") };
+    match std::fs::DirBuilder::new().mode(0o777).create(&dir) {
+        // the umask took some of the bits away
+        Ok(()) => std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o1777))?,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    // anyone could have made it first, so make sure it can't be used to swap lock files
+    // out from under us
+    let metadata = dir.symlink_metadata()?;
+    let owner = metadata.uid();
+    if !metadata.is_dir() || metadata.mode() & 0o1000 == 0 || (owner != 0 && owner != unsafe { libc::geteuid() }) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} isn't a sticky directory owned by root or you", dir.display())));
+    }
+    Ok(dir)
+}
+
+/// names the lock file for a path. the path itself could be too long for a file name, so
+/// this is an fnv-1a hash of it, which stays the same between builds unlike `DefaultHasher`
+fn lock_name(path: &Path) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in path.as_os_str().as_bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}.lock", hash)
+}
+
+/// opens a lock file, only changing its permissions if we're the one who made it, so a link
+/// someone else left there can't be used to change anything else's
+fn open(file: &Path) -> io::Result<File> {
+    let created = File::options().read(true).write(true).create_new(true).custom_flags(libc::O_NOFOLLOW).mode(0o666).open(file);
+    match created {
+        Ok(f) => {
+            // so other users can open it to lock it too
+            f.set_permissions(std::fs::Permissions::from_mode(0o666))?;
+            Ok(f)
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => File::options().read(true).custom_flags(libc::O_NOFOLLOW).open(file),
+        Err(e) => Err(e),
+    }
+}
+
+fn flock(dir: &Path, path: &Path, operation: libc::c_int) -> io::Result<(PathBuf, File)> {
+    let name = dir.join(lock_name(path));
+    loop {
+        let file = open(&name)?;
+        if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // whoever had it last may have removed it between us opening and locking it, in which
+        // case the lock is on a file nobody else will find, so go again with the new one
+        let current = std::fs::symlink_metadata(&name);
+        let locked = file.metadata()?;
+        if current.is_ok_and(|c| c.dev() == locked.dev() && c.ino() == locked.ino()) {
+            return Ok((name, file));
+        }
+    }
+}
+
+impl Lock {
+    /// locks a canonical path, failing with `WouldBlock` if another run holds an overlapping lock
+    pub fn acquire(path: &Path) -> io::Result<Lock> {
+        let dir = lock_dir()?;
+        // pushed as they're taken, so whatever was locked before a failure is cleaned up too
+        let mut lock = Lock { files: Vec::new() };
+        for parent in path.ancestors().skip(1) {
+            lock.files.push(flock(&dir, parent, libc::LOCK_SH)?);
+        }
+        lock.files.push(flock(&dir, path, libc::LOCK_EX)?);
+        Ok(lock)
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // a file someone else still has locked is theirs to remove, and one someone else made
+        // can't be removed by us anyway, which only leaves an empty file behind
+        for (name, file) in self.files.drain(..).rev() {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+                let _ = std::fs::remove_file(&name);
+            }
+        }
+    }
+}
@@ -1,168 +1,116 @@
-use std::mem::MaybeUninit;
-use std::os::unix::prelude::*;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use rce::*;
+use saferm::*;
 
-pub struct DeleteOptions {
-    pub recursive: bool,
-    pub umount: bool,
-    pub dryrun: bool,
-    pub allow_delete_above_start: bool,
-    pub enter_symlinks: bool,
-    pub verbose: bool,
-    pub allow_hidden_files: bool,
-    pub remove_symlinks: bool,
-    pub starting_dir: PathBuf,
+/// how many leftovers `--verify-empty` lists before it stops
+const VERIFY_EMPTY_SHOWN: usize = 20;
+
+/// the directories listed in a `--keep-structure-file`, one per line and relative to `start`
+fn keep_structure(start: &Path, contents: &[u8]) -> std::collections::HashSet<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    contents
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| start.join(std::ffi::OsStr::from_bytes(line)))
+        .collect()
 }
 
-/// returns true if the path has something mounted
-fn is_mountpoint(path: &Path) -> bool {
-    use libc::*;
-    let mut statbuf = MaybeUninit::<stat>::uninit();
-    let mut statbuf2 = MaybeUninit::<stat>::uninit();
-    let path_cstr = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
-    let parent = path.parent();
-    // if there isn't a parent, this is the root directory, and it's a mountpoint
-    let parent_cstr = match parent {
-        Some(p) => std::ffi::CString::new(p.as_os_str().as_bytes()).unwrap(),
-        None => return true,
-    };
-    unsafe {
-        if lstat(path_cstr.as_ptr(), statbuf.as_mut_ptr()) != 0 {
-            return false;
-        }
-        if lstat(parent_cstr.as_ptr(), statbuf2.as_mut_ptr()) != 0 {
-            return false;
-        }
-        let statbuf = statbuf.assume_init();
-        let statbuf2 = statbuf2.assume_init();
-        statbuf.st_dev != statbuf2.st_dev
+/// points stdout at /dev/null, for `--quiet`
+fn quiet() -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let null = std::fs::OpenOptions::new().write(true).open("/dev/null")?;
+    if unsafe { libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(())
 }
 
-/// unmounts a path, and if that fails then tries again lazily before panicing
-fn unmount(path: &Path) {
-    use libc::*;
-    let path_cstr = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
-    let mut flags = 0;
-    // this will be different on macos and linux
-    #[cfg(target_os = "linux")]
-    {
-        flags = MNT_DETACH;
-    }
-    #[cfg(target_os = "macos")]
-    {
-        flags = MNT_FORCE;
-    }
-    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-    {
-        panic!("unmount not implemented for this platform");
+/// prints the library's log messages to stderr, so they stay out of the way of the normal output
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
     }
-    #[cfg(target_os = "linux")]
-    {
-        if unsafe { umount2(path_cstr.as_ptr(), flags) } != 0 {
-            if unsafe { umount2(path_cstr.as_ptr(), flags | MNT_FORCE) } != 0 {
-                panic!("failed to unmount {}", path.display());
-            }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level().as_str().to_lowercase(), record.args());
         }
     }
-    #[cfg(target_os = "macos")]
-    {
-        if unsafe { unmount(path_cstr.as_ptr(), flags) } != 0 {
-            panic!("failed to unmount {}", path.display());
-        }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// asks the user to type out a path, returns true if it matches exactly
+fn confirm_path(path: &Path) -> bool {
+    use std::io::Write;
+    println!("this will delete {}", path.display());
+    print!("type the full path to confirm: ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
     }
+    answer.trim_end_matches(['\n', '\r']) == path.as_os_str()
 }
 
-fn delete(path: &str, options: &DeleteOptions) -> Result<(), ()> {
-    let path = Path::new(path);
-    // check if path is a symlink
-    if path.is_symlink() {
-        // if we are supposed to remove symlinks, remove it
-        if options.remove_symlinks {
-            println!("removing symlink {}", path.display());
-            if !options.dryrun {
-                std::fs::remove_file(path).unwrap();
-            } else {
-                println!("(dryrun) did nothing");
-            }
-            return Ok(());
-        }
-        if !options.enter_symlinks {
-            println!("{} is a symlink, skipping", path.display());
-            return Err(());
-        }
-    }
-    // check if path is above starting dir
-    if !options.allow_delete_above_start {
-        if !path.canonicalize().unwrap().starts_with(&options.starting_dir) {
-            println!("{} is above starting dir, skipping", path.display());
-            return Err(());
-        }
-    }
-    // check if this is a hidden file or directory
-    if !options.allow_hidden_files {
-        if path.file_name().unwrap_or("".as_ref()).to_str().unwrap_or("").starts_with(".") {
-            println!("{} is a hidden file, skipping", path.display());
-            return Err(());
-        }
-    }
-    // check if path is a mount point
-    if is_mountpoint(path) {
-        if options.umount {
-            println!("{} is a mount point, unmounting", path.display());
-            if !options.dryrun {
-                unmount(path);
-            } else {
-                println!("(dryrun) did nothing");
-            }
-            return Ok(());
-        } else {
-            println!("{} is a mount point, skipping", path.display());
-            return Err(());
-        }
-    }
-    // check if path is a directory
-    if path.is_dir() {
-        if options.recursive {
-            if options.verbose { println!("{} is a directory, recursing", path.display()); }
-            for entry in std::fs::read_dir(path).unwrap() {
-                // check if symlink
-                let entry = entry.unwrap();
-                let path = entry.path();
-                let _ = delete(path.to_str().unwrap(), options);
-            }
-            // if directory is empty, remove it
-            if std::fs::read_dir(path).unwrap().next().is_none() {
-                println!("removing directory {}", path.display());
-                if !options.dryrun {
-                    std::fs::remove_dir(path).unwrap();
-                } else {
-                    println!("(dryrun) did nothing");
+/// moves what was trashed from `path`, or under the name `name`, back out of the trash. when
+/// there's more than one, asks which unless told to take the latest
+fn restore(trash: &trash::Trash, path: &Path, name: &str, latest: bool) {
+    let entries = match trash.entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("error: couldn't read the trash");
+            println!("  {}", e);
+            std::process::exit(6);
+        }
+    };
+    let mut matches: Vec<_> = entries.into_iter().filter(|e| e.info.path == path || e.name == name).collect();
+    let entry = match matches.len() {
+        0 => {
+            println!("error: nothing in the trash was at {} or is called {}", path.display(), name);
+            std::process::exit(3);
+        }
+        1 => matches.remove(0),
+        // they're oldest first
+        _ if latest => matches.pop().unwrap(),
+        n => {
+            println!("{} versions of {} are in the trash:", n, path.display());
+            for (i, entry) in matches.iter().enumerate() {
+                match entry.info.version {
+                    Some(version) => println!("  {}) version {} trashed {}", i + 1, version, entry.info.deletion_date),
+                    None => println!("  {}) {} trashed {}", i + 1, entry.name, entry.info.deletion_date),
+                }
+            }
+            use std::io::Write;
+            print!("which one? [1-{}] ", n);
+            let _ = std::io::stdout().flush();
+            let mut answer = String::new();
+            let _ = std::io::stdin().read_line(&mut answer);
+            match answer.trim().parse::<usize>() {
+                Ok(i) if (1..=n).contains(&i) => matches.remove(i - 1),
+                _ => {
+                    println!("aborting");
+                    std::process::exit(2);
                 }
-                return Ok(());
-            } else {
-                println!("{} is not empty after recursion, skipping", path.display());
-                return Err(());
             }
-        } else {
-            println!("{} is a directory, skipping", path.display());
-            return Err(());
         }
-    }
-    // delete path
-    if options.verbose { println!("deleting {}", path.display()); }
-    if !options.dryrun {
-        let res = std::fs::remove_file(path);
-        if res.is_err() {
-            println!("error deleting {}: {}", path.display(), res.unwrap_err());
+    };
+    match trash.restore(&entry) {
+        Ok(()) => println!("restored {} to {}", entry.name, entry.info.path.display()),
+        Err(e) => {
+            println!("error restoring {}: {}", entry.info.path.display(), e);
+            std::process::exit(error::ErrorCategory::of(&e).exit_code());
         }
-    } else if options.verbose { println!("(dryrun) did nothing"); }
-
-    Ok(())
+    }
 }
 
 fn main() {
+    let start_time = SystemTime::now();
     let version = env!("CARGO_PKG_VERSION");
     let mut cmd = CommandInterface::new(
         "saferm",
@@ -174,6 +122,74 @@ Licensed under Apache 2.0", version).as_str(),
     );
 
     let a_path = cmd.add_argument(Invoker::NWithoutInvoker(0), "path");
+    let a_expect_count = cmd.add_argument(Invoker::DoubleDash("expect-count"), "n");
+    let a_expect_tolerance = cmd.add_argument(Invoker::DoubleDash("expect-tolerance"), "percent");
+    let a_trash_dir = cmd.add_argument(Invoker::DoubleDash("trash-dir"), "dir");
+    let a_trash_max_size = cmd.add_argument(Invoker::DoubleDash("trash-max-size"), "size");
+    let a_trash_fallback = cmd.add_argument(Invoker::DoubleDash("trash-fallback"), "permanent|abort|keep");
+    let a_trash_async_jobs = cmd.add_argument(Invoker::DoubleDash("trash-async-jobs"), "n");
+    let a_backup = cmd.add_argument(Invoker::DoubleDash("backup"), "dir");
+    let a_move_to = cmd.add_argument(Invoker::DoubleDash("move-to"), "dir");
+    let a_rename = cmd.add_argument(Invoker::DoubleDash("rename"), "template");
+    let a_on_collision = cmd.add_argument(Invoker::DoubleDash("on-collision"), "suffix|skip");
+    let a_until_free = cmd.add_argument(Invoker::DoubleDash("until-free"), "size");
+    let a_until_free_inodes = cmd.add_argument(Invoker::DoubleDash("until-free-inodes"), "n");
+    let a_until_project_below = cmd.add_argument(Invoker::DoubleDash("until-project-below"), "project:size");
+    let f_quota_aware = cmd.add_flag(Invoker::DoubleDash("quota-aware"), "report how much was freed in each quota project");
+    let a_regex = cmd.add_argument(Invoker::DoubleDash("regex"), "pattern");
+    let a_exclude_regex = cmd.add_argument(Invoker::DoubleDash("exclude-regex"), "pattern");
+    let a_placeholder_content = cmd.add_argument(Invoker::DoubleDash("placeholder-content"), "text");
+    #[cfg(all(unix, feature = "syslog"))]
+    let a_syslog_priority = cmd.add_argument(Invoker::DoubleDash("syslog-priority"), "priority");
+    #[cfg(all(unix, feature = "syslog"))]
+    let a_syslog_tag = cmd.add_argument(Invoker::DoubleDash("syslog-tag"), "tag");
+    let a_partition_by_error = cmd.add_argument(Invoker::DoubleDash("partition-by-error"), "dir");
+    let a_hash_manifest = cmd.add_argument(Invoker::DoubleDash("hash-manifest"), "file");
+    let a_sync_every = cmd.add_argument(Invoker::DoubleDash("sync-every"), "n");
+    let a_keep_structure_file = cmd.add_argument(Invoker::DoubleDash("keep-structure-file"), "file");
+    let a_include_at_depth = cmd.add_argument(Invoker::DoubleDash("include-at-depth"), "depth:pattern");
+    let a_exclude_at_depth = cmd.add_argument(Invoker::DoubleDash("exclude-at-depth"), "depth:pattern");
+    let a_normalize_unicode = cmd.add_argument(Invoker::DoubleDash("normalize-unicode"), "nfc|nfd|none");
+    let a_checkpoint = cmd.add_argument(Invoker::DoubleDash("checkpoint"), "file");
+    let a_owner = cmd.add_argument(Invoker::DoubleDash("owner"), "user");
+    let a_group = cmd.add_argument(Invoker::DoubleDash("group"), "group");
+    let a_device_whitelist = cmd.add_argument(Invoker::DoubleDash("device-whitelist"), "mount|major:minor,...");
+    let a_device_blacklist = cmd.add_argument(Invoker::DoubleDash("device-blacklist"), "mount|major:minor,...");
+    let a_deadline = cmd.add_argument(Invoker::DoubleDash("deadline"), "duration");
+    let a_dotfiles = cmd.add_argument(Invoker::DoubleDash("dotfiles"), "skip|delete|prompt");
+    let a_walk_order = cmd.add_argument(Invoker::DoubleDash("walk-order"), "preorder|postorder");
+    let a_on_mountpoint = cmd.add_argument(Invoker::DoubleDash("on-mountpoint"), "skip|unmount|fail");
+    let a_follow_bind_mounts = cmd.add_argument(Invoker::DoubleDash("follow-bind-mounts"), "true|false");
+    let a_unmount_failure = cmd.add_argument(Invoker::DoubleDash("unmount-failure"), "abort|skip");
+    let a_dry_run_diff = cmd.add_argument(Invoker::DoubleDash("dry-run-diff"), "file");
+    let a_tmpfiles = cmd.add_argument(Invoker::DoubleDash("tmpfiles"), "age");
+    let a_age_by = cmd.add_argument(Invoker::DoubleDash("age-by"), "atime,mtime,ctime");
+    let a_not_accessed_since = cmd.add_argument(Invoker::DoubleDash("not-accessed-since"), "duration");
+    let a_report_largest = cmd.add_argument(Invoker::DoubleDash("report-largest"), "n");
+    let a_max_errors = cmd.add_argument(Invoker::DoubleDash("max-errors"), "n");
+    let a_abort_if_above = cmd.add_argument(Invoker::DoubleDash("abort-if-above"), "count");
+    let f_dangling_hardlink_report = cmd.add_flag(
+        Invoker::DoubleDash("dangling-hardlink-report"),
+        "list the deleted files that still have hardlinks elsewhere, so their data wasn't freed",
+    );
+    let a_plan_file = cmd.add_argument(Invoker::DoubleDash("plan-file"), "file");
+    let f_tree = cmd.add_flag(Invoker::DoubleDash("tree"), "show what would be deleted as a tree with sizes and stop");
+    let f_heatmap = cmd.add_flag(Invoker::DoubleDash("heatmap"), "color the sizes in --tree from green to red");
+    let a_heatmap_thresholds = cmd.add_argument(Invoker::DoubleDash("heatmap-thresholds"), "small:large");
+    let a_color = cmd.add_argument(Invoker::DoubleDash("color"), "auto|always|never");
+    let a_preview = cmd.add_argument(Invoker::DoubleDash("preview"), "n");
+    let a_reflink = cmd.add_argument(Invoker::DoubleDash("reflink"), "auto|always|never");
+    let a_path_filter = cmd.add_argument(Invoker::DoubleDash("path-filter"), "command");
+    let a_bench_width = cmd.add_argument(Invoker::DoubleDash("bench-width"), "n");
+    let a_bench_depth = cmd.add_argument(Invoker::DoubleDash("bench-depth"), "n");
+    let a_bench_file_size = cmd.add_argument(Invoker::DoubleDash("bench-file-size"), "size");
+    let a_min_depth = cmd.add_argument(Invoker::DoubleDash("min-depth"), "n");
+    let a_keep = cmd.add_argument(Invoker::DoubleDash("keep"), "oldest|newest");
+    let a_report_format = cmd.add_argument(Invoker::DoubleDash("report-format"), "text|json|csv");
+    #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+    let a_simulate_error = cmd.add_argument(Invoker::DoubleDash("simulate-error"), "fraction");
+    let a_log_level = cmd.add_argument(Invoker::DoubleDash("log-level"), "off|error|warn|info|debug|trace");
+    let a_order = cmd.add_argument(Invoker::DoubleDash("order"), "oldest|newest|largest|lru");
 
     let f_help = cmd.add_flag(
         Invoker::DashAndDoubleDash("h", "help"),
@@ -190,7 +206,7 @@ Licensed under Apache 2.0", version).as_str(),
     );
     let f_umount = cmd.add_flag(
         Invoker::DashAndDoubleDash("u", "umount"),
-        "unmount all found mount points"
+        "unmount all found mount points, the same as --on-mountpoint unmount"
     );
     let f_enter_symlinks = cmd.add_flag(
         Invoker::DashAndDoubleDash("s", "enter-symlinks"),
@@ -204,17 +220,286 @@ Licensed under Apache 2.0", version).as_str(),
         Invoker::DashAndDoubleDash("a", "allow-delete-above-start"),
         "allow deleting files above the directory specified"
     );
+    let f_strict_canonicalize = cmd.add_flag(
+        Invoker::DoubleDash("strict-canonicalize"),
+        "report what something else removes while we're walking as an error instead of skipping it"
+    );
     let f_allow_hidden_files = cmd.add_flag(
         Invoker::DashAndDoubleDash("ah", "allow-hidden-files"),
-        "allow deleting hidden files/folders"
+        "allow deleting hidden files/folders, the same as --dotfiles delete"
     );
     let f_verbose = cmd.add_flag(
         Invoker::DashAndDoubleDash("v", "verbose"),
         "print more information"
     );
+    let f_dir_summary = cmd.add_flag(
+        Invoker::DashAndDoubleDash("ds", "dir-summary"),
+        "print a summary line as each directory is finished"
+    );
+    let f_trash = cmd.add_flag(
+        Invoker::DashAndDoubleDash("t", "trash"),
+        "move files to the trash instead of deleting them"
+    );
+    let f_exclude_newer_than_start = cmd.add_flag(
+        Invoker::DoubleDash("exclude-newer-than-start"),
+        "skip files modified after saferm started"
+    );
+    let f_permissions_report = cmd.add_flag(
+        Invoker::DoubleDash("permissions-report"),
+        "list everything that couldn't be deleted due to permissions, then exit"
+    );
+    let f_parents_must_exist = cmd.add_flag(
+        Invoker::DoubleDash("parents-must-exist"),
+        "if the path doesn't exist, suggest similar paths that do"
+    );
+    let f_confirm_path = cmd.add_flag(
+        Invoker::DoubleDash("confirm-path"),
+        "require typing the full path being deleted before starting"
+    );
+    let f_yes = cmd.add_flag(
+        Invoker::DashAndDoubleDash("y", "yes"),
+        "skip --confirm-path, --prompt-summary-before and --abort-if-above, for automation"
+    );
+    let f_hardlink_instead_of_copy = cmd.add_flag(
+        Invoker::DoubleDash("hardlink-instead-of-copy"),
+        "hardlink files into the --backup directory when it's on the same device"
+    );
+    let f_preserve_structure = cmd.add_flag(
+        Invoker::DoubleDash("preserve-structure"),
+        "keep the directory structure under the --move-to directory"
+    );
+    let f_regex_full_path = cmd.add_flag(
+        Invoker::DoubleDash("regex-full-path"),
+        "match --regex and --exclude-regex against the full path instead of the name"
+    );
+    let f_show_ids = cmd.add_flag(
+        Invoker::DoubleDash("show-ids"),
+        "show the device and inode of each entry next to it, and add them to --jsonl records"
+    );
+    let f_only_if_owned_and_writable = cmd.add_flag(
+        Invoker::DoubleDash("only-if-owned-and-writable"),
+        "only delete files you own that are in directories you can write to"
+    );
+    let f_ignore_case = cmd.add_flag(
+        Invoker::DashAndDoubleDash("I", "ignore-case"),
+        "match every pattern without caring about case, so \\.log$ matches .LOG too"
+    );
+    let f_skip_open = cmd.add_flag(
+        Invoker::DoubleDash("skip-open"),
+        "skip files that another process has open"
+    );
+    let f_gitignore = cmd.add_flag(
+        Invoker::DoubleDash("gitignore"),
+        "only delete files ignored by the .gitignore files of the repository, like git clean -X"
+    );
+    let f_refuse_if_git_tracked = cmd.add_flag(
+        Invoker::DoubleDash("refuse-if-git-tracked"),
+        "never delete files git tracks, when inside a git repository"
+    );
+    let f_allow_tracked = cmd.add_flag(
+        Invoker::DoubleDash("allow-tracked"),
+        "delete files git tracks after all, overriding --refuse-if-git-tracked"
+    );
+    let f_force_gitignore = cmd.add_flag(
+        Invoker::DoubleDash("force-gitignore"),
+        "use --gitignore even outside of a git repository"
+    );
+    let f_verify_empty = cmd.add_flag(
+        Invoker::DoubleDash("verify-empty"),
+        "fail if anything is left of the path once we're done"
+    );
+    let f_no_lock = cmd.add_flag(
+        Invoker::DoubleDash("no-lock"),
+        "don't check for other saferm runs on overlapping paths"
+    );
+    let f_replace_with_placeholder = cmd.add_flag(
+        Invoker::DoubleDash("replace-with-placeholder"),
+        "leave an empty file with the same permissions in place of each deleted file"
+    );
+    let f_dedupe = cmd.add_flag(
+        Invoker::DoubleDash("dedupe"),
+        "only delete files with the same content as another one, keeping one of each"
+    );
+    let f_swap_empty = cmd.add_flag(
+        Invoker::DoubleDash("swap-empty"),
+        "swap the directory for an empty one first, then delete the old contents"
+    );
+    let f_preserve_top = cmd.add_flag(
+        Invoker::DoubleDash("preserve-top"),
+        "delete everything in the directory but keep the directory itself"
+    );
+    let f_preserve_newest = cmd.add_flag(
+        Invoker::DoubleDash("preserve-newest-in-each-dir"),
+        "keep the newest file that would be deleted in every directory"
+    );
+    let f_keep_root = cmd.add_flag(
+        Invoker::DoubleDash("keep-root"),
+        "the same as --preserve-top"
+    );
+    let f_trash_info_extra = cmd.add_flag(
+        Invoker::DoubleDash("trash-info-extra"),
+        "also record permissions, owner and mtime in the .trashinfo files"
+    );
+    let f_apply_plan = cmd.add_flag(
+        Invoker::DoubleDash("apply-plan"),
+        "do what a --plan-file says, the path is the plan file"
+    );
+    let f_no_cross_user_dirs = cmd.add_flag(
+        Invoker::DoubleDash("no-cross-user-dirs"),
+        "don't go into directories owned by anyone but the owner of the path, the default as root"
+    );
+    let f_allow_cross_user = cmd.add_flag(
+        Invoker::DoubleDash("allow-cross-user"),
+        "go into directories owned by other users, even as root"
+    );
+    let f_abort_on_symlink_escape = cmd.add_flag(
+        Invoker::DoubleDash("abort-on-symlink-escape"),
+        "stop everything if a symlink points outside of the path"
+    );
+    #[cfg(feature = "compress")]
+    let f_trash_compress = cmd.add_flag(
+        Invoker::DoubleDash("trash-compress"),
+        "gzip files on the way into the trash, other trash tools won't know to decompress them"
+    );
+    let f_trash_versioned = cmd.add_flag(
+        Invoker::DoubleDash("trash-versioned"),
+        "keep every version of a name in the trash in a folder of its own, other trash tools won't see them"
+    );
+    let f_skip_if_newer_in_trash = cmd.add_flag(
+        Invoker::DoubleDash("skip-if-newer-in-trash"),
+        "delete a file for good instead of trashing it if the trash has a copy from the same path that's no older"
+    );
+    let f_interactive_editor = cmd.add_flag(
+        Invoker::DoubleDash("interactive-editor"),
+        "pick what gets deleted by editing the list in $EDITOR"
+    );
+    let f_interactive_filter_repl = cmd.add_flag(
+        Invoker::DoubleDash("interactive-filter-repl"),
+        "narrow down what gets deleted with filters at a prompt, then commit"
+    );
+    let f_no_descend_mountpoints = cmd.add_flag(
+        Invoker::DoubleDash("no-descend-mountpoints"),
+        "clean a mount point you start on, but stop at anything mounted below it"
+    );
+    let f_skip_device_files = cmd.add_flag(
+        Invoker::DoubleDash("skip-device-files-on-other-fs"),
+        "leave device nodes, sockets and fifos alone if they're on another filesystem than the path"
+    );
+    let f_parallel_stat = cmd.add_flag(
+        Invoker::DoubleDash("parallel-stat"),
+        "look at the files in each directory a few at a time, faster on network filesystems"
+    );
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    let f_io_uring = cmd.add_flag(
+        Invoker::DoubleDash("io-uring"),
+        "unlink files in batches through io_uring, falling back to one at a time if it's not there"
+    );
+    let f_trace_decisions = cmd.add_flag(
+        Invoker::DoubleDash("trace-decisions"),
+        "print every check each file goes through and what was decided"
+    );
+    let f_jsonl = cmd.add_flag(
+        Invoker::DoubleDash("jsonl"),
+        "print a json object per line to stdout for everything done, everything else goes to stderr"
+    );
+    let f_quiet = cmd.add_flag(
+        Invoker::DashAndDoubleDash("q", "quiet"),
+        "print nothing to stdout, for when the exit code, --jsonl or --syslog are all that matter"
+    );
+    #[cfg(all(unix, feature = "syslog"))]
+    let f_syslog = cmd.add_flag(
+        Invoker::DoubleDash("syslog"),
+        "send the summary to the system log"
+    );
+    #[cfg(all(unix, feature = "syslog"))]
+    let f_syslog_each = cmd.add_flag(
+        Invoker::DoubleDash("syslog-each"),
+        "with --syslog, send a line for everything deleted too"
+    );
+    #[cfg(target_os = "linux")]
+    let f_clear_attrs = cmd.add_flag(
+        Invoker::DoubleDash("clear-attrs"),
+        "clear the immutable and append-only attributes of what they keep from being deleted"
+    );
+    let f_null = cmd.add_flag(
+        Invoker::DashAndDoubleDash("0", "null"),
+        "end each entry in the --partition-by-error files with a nul instead of a newline"
+    );
+    let f_prompt_summary_before = cmd.add_flag(
+        Invoker::DoubleDash("prompt-summary-before"),
+        "show what would be deleted and ask before doing it"
+    );
+    let f_count_only = cmd.add_flag(
+        Invoker::DoubleDash("count-only"),
+        "print how many files would be deleted and stop"
+    );
+    let f_count = cmd.add_flag(
+        Invoker::DoubleDash("count"),
+        "the same as --count-only"
+    );
+    let f_skip_histogram = cmd.add_flag(
+        Invoker::DoubleDash("report-skipped-reasons-histogram"),
+        "print how many things were skipped for each reason at the end, as a chart"
+    );
+    let f_summary_exit_code_from_freed_bytes = cmd.add_flag(
+        Invoker::DoubleDash("summary-exit-code-from-freed-bytes"),
+        "when nothing went wrong, exit with 20 if nothing was freed, 21 if under 1GiB was and 22 otherwise"
+    );
+    let f_summary_only_on_change = cmd.add_flag(
+        Invoker::DoubleDash("summary-only-on-change"),
+        "print a summary at the end, but only if something was deleted or went wrong"
+    );
+    let f_accurate_size = cmd.add_flag(
+        Invoker::DoubleDash("accurate-size"),
+        "count freed space by the blocks files use, so sparse files don't overstate it"
+    );
+    let f_detect_sparse = cmd.add_flag(
+        Invoker::DoubleDash("detect-sparse"),
+        "the same as --accurate-size"
+    );
+    let f_two_phase = cmd.add_flag(
+        Invoker::DoubleDash("two-phase"),
+        "check everything removed is really gone after the run, trying again if not"
+    );
+    let f_with_targets = cmd.add_flag(
+        Invoker::DoubleDash("with-targets"),
+        "with -rs, also delete what removed symlinks pointed to, if it's in the starting dir"
+    );
+    let f_bench = cmd.add_flag(
+        Invoker::DoubleDash("bench"),
+        "time deleting a made up tree in a new directory under path, with the other options"
+    );
+    let f_selftest = cmd.add_flag(
+        Invoker::DoubleDash("selftest"),
+        "check the safety checks hold on this system, in a new directory under path"
+    );
+    let f_confirm_each_dir = cmd.add_flag(
+        Invoker::DoubleDash("confirm-each-dir-with-preview"),
+        "show what's in each directory and ask before deleting its contents"
+    );
+    let f_restore = cmd.add_flag(
+        Invoker::DoubleDash("restore"),
+        "move path back out of the trash instead of deleting it, path can also be its name in the trash"
+    );
+    let f_restore_latest = cmd.add_flag(
+        Invoker::DoubleDash("restore-latest"),
+        "with --restore, take the most recently trashed version without asking"
+    );
+    let f_prune_broken_symlinks = cmd.add_flag(
+        Invoker::DoubleDash("prune-broken-symlinks"),
+        "only remove symlinks whose target doesn't exist"
+    );
+    let f_trash_async = cmd.add_flag(
+        Invoker::DoubleDash("trash-move-across-devices-async"),
+        "copy files into a trash on another device in the background"
+    );
+    let f_trash_purge_oldest = cmd.add_flag(
+        Invoker::DoubleDash("trash-purge-oldest"),
+        "purge the oldest trashed items when the trash is full"
+    );
 
 
-    let c_default = cmd.add_command(
+    let _c_default = cmd.add_command(
         Invoker::Default,
         vec![a_path],
         "delete a file"
@@ -233,17 +518,512 @@ Licensed under Apache 2.0", version).as_str(),
         return;
     }
 
+    // -v is the only verbosity we have, so it turns on info and --log-level goes further
+    let log_level = match input.arguments.get(&a_log_level).map(|l| l.parse::<log::LevelFilter>()) {
+        Some(Ok(level)) => level,
+        Some(Err(_)) => {
+            println!("error: --log-level must be one of off, error, warn, info, debug or trace");
+            return;
+        }
+        None if input.flags.contains(&f_verbose) => log::LevelFilter::Info,
+        None => log::LevelFilter::Warn,
+    };
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log_level);
+    }
+
+    // before anything is printed, so the stream on stdout is only records
+    let jsonl = if input.flags.contains(&f_jsonl) {
+        match jsonl::Jsonl::take_stdout() {
+            Ok(mut jsonl) => {
+                jsonl.ids = input.flags.contains(&f_show_ids);
+                Some(jsonl)
+            }
+            Err(e) => {
+                println!("error: couldn't set up --jsonl");
+                println!("  {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    if input.flags.contains(&f_quiet) {
+        if let Err(e) = quiet() {
+            println!("error: couldn't silence the output");
+            println!("  {}", e);
+            return;
+        }
+    }
+    #[cfg(all(unix, feature = "syslog"))]
+    let syslog = if input.flags.contains(&f_syslog) {
+        let priority = match input.arguments.get(&a_syslog_priority).map(|p| syslog::Syslog::parse_priority(p)) {
+            Some(Some(priority)) => priority,
+            Some(None) => {
+                println!("error: --syslog-priority must be one of emerg, alert, crit, err, warning, notice, info or debug");
+                return;
+            }
+            None => libc::LOG_INFO,
+        };
+        let tag = input.arguments.get(&a_syslog_tag).map_or("saferm", |t| t.as_str());
+        match syslog::Syslog::open(tag, priority, input.flags.contains(&f_syslog_each)) {
+            Some(syslog) => Some(syslog),
+            None => {
+                println!("error: --syslog-tag can't have a nul in it");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
     let path = input.inputs[0].clone();
-    let recursive = input.flags.contains(&f_recursive);
-    let umount = input.flags.contains(&f_umount);
+    let age_by = match input.arguments.get(&a_age_by).map(|b| age::Age::parse_by(b)) {
+        Some(Some(by)) => Some(by),
+        Some(None) => {
+            println!("error: --age-by must be a list of atime, mtime and ctime, like atime,mtime");
+            return;
+        }
+        None => None,
+    };
+    // like systemd-tmpfiles, this cleans out everything old in a directory but keeps the directory
+    let age = match input.arguments.get(&a_tmpfiles).map(|a| parse_duration(a)) {
+        Some(Some(age)) => {
+            let by = age_by.clone().unwrap_or_else(|| vec![age::Timestamp::Accessed, age::Timestamp::Modified, age::Timestamp::Changed]);
+            Some(age::Age::new(age, start_time, by))
+        }
+        Some(None) => {
+            println!("error: --tmpfiles must be an age, like 10d or 12h");
+            return;
+        }
+        None => None,
+    };
+    // for evicting from a cache, what's been read lately is what's still wanted
+    let not_accessed_since = match input.arguments.get(&a_not_accessed_since).map(|a| parse_duration(a)) {
+        Some(Some(age)) => Some(age::Age::new(age, start_time, age_by.unwrap_or_else(|| vec![age::Timestamp::Accessed]))),
+        Some(None) => {
+            println!("error: --not-accessed-since must be a duration, like 10d or 12h");
+            return;
+        }
+        None => None,
+    };
+    let tmpfiles = age.is_some();
+    let recursive = input.flags.contains(&f_recursive) || tmpfiles;
+    let mountpoints = match input.arguments.get(&a_on_mountpoint).map(|p| Mountpoints::parse(p)) {
+        Some(Some(policy)) => policy,
+        Some(None) => {
+            println!("error: --on-mountpoint must be one of skip, unmount or fail");
+            return;
+        }
+        None if input.flags.contains(&f_umount) => Mountpoints::Unmount,
+        None => Mountpoints::Skip,
+    };
+    let follow_bind_mounts = match input.arguments.get(&a_follow_bind_mounts).map(|b| b.parse::<bool>()) {
+        Some(Ok(follow)) => follow,
+        Some(Err(_)) => {
+            println!("error: --follow-bind-mounts must be true or false");
+            return;
+        }
+        None => false,
+    };
+    let unmount_failure = match input.arguments.get(&a_unmount_failure).map(|p| UnmountFailure::parse(p)) {
+        Some(Some(policy)) => Some(policy),
+        Some(None) => {
+            println!("error: --unmount-failure must be abort or skip");
+            return;
+        }
+        None => None,
+    };
     let dryrun = input.flags.contains(&f_dryrun);
     let allow_delete_above_start = input.flags.contains(&f_allow_delete_above_start);
     let enter_symlinks = input.flags.contains(&f_enter_symlinks);
     let verbose = input.flags.contains(&f_verbose);
-    let allow_hidden_files = input.flags.contains(&f_allow_hidden_files);
-    let remove_symlinks = input.flags.contains(&f_remove_symlinks);
+    let walk_order = match input.arguments.get(&a_walk_order).map(|o| WalkOrder::parse(o)) {
+        Some(Some(order)) => Some(order),
+        Some(None) => {
+            println!("error: --walk-order must be one of preorder or postorder");
+            return;
+        }
+        None => None,
+    };
+    let dotfiles = match input.arguments.get(&a_dotfiles).map(|p| Dotfiles::parse(p)) {
+        Some(Some(policy)) => policy,
+        Some(None) => {
+            println!("error: --dotfiles must be one of skip, delete or prompt");
+            return;
+        }
+        None if input.flags.contains(&f_allow_hidden_files) || tmpfiles => Dotfiles::Delete,
+        None => Dotfiles::Skip,
+    };
+    let remove_symlinks = input.flags.contains(&f_remove_symlinks) || tmpfiles;
+    let dir_summary = input.flags.contains(&f_dir_summary);
+    let exclude_newer_than_start = input.flags.contains(&f_exclude_newer_than_start);
+    let expect_count = match input.arguments.get(&a_expect_count).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => Some(n),
+        Some(Err(_)) => {
+            println!("error: --expect-count must be a number");
+            return;
+        }
+        None => None,
+    };
+    let expect_tolerance = match input.arguments.get(&a_expect_tolerance).map(|n| n.parse::<f64>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            println!("error: --expect-tolerance must be a number");
+            return;
+        }
+        None => 10.0,
+    };
+    let report_largest = match input.arguments.get(&a_report_largest).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            println!("error: --report-largest must be a number");
+            return;
+        }
+        None => 0,
+    };
+    let max_errors = match input.arguments.get(&a_max_errors).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            println!("error: --max-errors must be a number above 0");
+            return;
+        }
+        None => None,
+    };
+    let min_depth = match input.arguments.get(&a_min_depth).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            println!("error: --min-depth must be a number");
+            return;
+        }
+        None => 0,
+    };
+    let owner = match input.arguments.get(&a_owner).map(|u| (u, owner::lookup_user(u))) {
+        Some((_, Some(uid))) => Some(uid),
+        Some((user, None)) => {
+            println!("error: no such user {}", user);
+            return;
+        }
+        None => None,
+    };
+    let group = match input.arguments.get(&a_group).map(|g| (g, owner::lookup_group(g))) {
+        Some((_, Some(gid))) => Some(gid),
+        Some((name, None)) => {
+            println!("error: no such group {}", name);
+            return;
+        }
+        None => None,
+    };
+    let mut devices = None;
+    for (argument, name) in [(&a_device_whitelist, "--device-whitelist"), (&a_device_blacklist, "--device-blacklist")] {
+        let Some(list) = input.arguments.get(argument) else { continue };
+        match devices::Devices::parse_list(list) {
+            Ok(list) => {
+                let devices = devices.get_or_insert_with(devices::Devices::default);
+                if argument == &a_device_whitelist {
+                    devices.allowed = Some(list);
+                } else {
+                    devices.denied = list;
+                }
+            }
+            Err(e) => {
+                println!("error: {} must be paths or major:minor numbers, separated by commas", name);
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+    let deadline = match input.arguments.get(&a_deadline).map(|d| parse_duration(d)) {
+        Some(Some(deadline)) => Some(deadline),
+        Some(None) => {
+            println!("error: --deadline must be a duration, like 30s, 10m or 2h");
+            return;
+        }
+        None => None,
+    };
+    let keep = match input.arguments.get(&a_keep).map(|k| dedupe::Keep::parse(k)) {
+        Some(Some(keep)) => keep,
+        Some(None) => {
+            println!("error: --keep must be one of oldest or newest");
+            return;
+        }
+        None => dedupe::Keep::Oldest,
+    };
+    let dedupe = input.flags.contains(&f_dedupe);
+    let until_free_given = input.arguments.contains_key(&a_until_free)
+        || input.arguments.contains_key(&a_until_free_inodes)
+        || input.arguments.contains_key(&a_until_project_below);
+    if dedupe && until_free_given {
+        println!("error: --dedupe and --until-free can't be used together");
+        return;
+    }
+    if input.arguments.contains_key(&a_until_free) && input.arguments.contains_key(&a_until_free_inodes) {
+        println!("error: --until-free and --until-free-inodes can't be used together");
+        return;
+    }
+    if input.arguments.contains_key(&a_until_project_below) && (input.arguments.contains_key(&a_until_free) || input.arguments.contains_key(&a_until_free_inodes)) {
+        println!("error: --until-project-below can't be used with --until-free or --until-free-inodes");
+        return;
+    }
+    let report_format = match input.arguments.get(&a_report_format).map(|f| summary::Format::parse(f)) {
+        Some(Some(format)) => Some(format),
+        Some(None) => {
+            println!("error: --report-format must be one of text, json or csv");
+            return;
+        }
+        None => None,
+    };
+    let trash_max_size = match input.arguments.get(&a_trash_max_size).map(|n| parse_size(n)) {
+        Some(Some(n)) => Some(n),
+        Some(None) => {
+            println!("error: --trash-max-size must be a size");
+            return;
+        }
+        None => None,
+    };
+    let trash_fallback = match input.arguments.get(&a_trash_fallback).map(|f| trash::Fallback::parse(f)) {
+        Some(Some(fallback)) => fallback,
+        Some(None) => {
+            println!("error: --trash-fallback must be one of permanent, abort or keep");
+            return;
+        }
+        None => trash::Fallback::Keep,
+    };
+    let reflink = match input.arguments.get(&a_reflink).map(|r| reflink::Reflink::parse(r)) {
+        Some(Some(reflink)) => reflink,
+        Some(None) => {
+            println!("error: --reflink must be one of auto, always or never");
+            return;
+        }
+        None => reflink::Reflink::Never,
+    };
+    let backup = match input.arguments.get(&a_backup) {
+        Some(dir) => match backup::Backup::open(PathBuf::from(dir), input.flags.contains(&f_hardlink_instead_of_copy)) {
+            Ok(mut backup) => {
+                backup.reflink = reflink;
+                Some(backup)
+            }
+            Err(e) => {
+                println!("error: couldn't open the backup directory");
+                println!("  {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let collision = match input.arguments.get(&a_on_collision).map(|c| move_to::Collision::parse(c)) {
+        Some(Some(collision)) => collision,
+        Some(None) => {
+            println!("error: --on-collision must be one of suffix or skip");
+            return;
+        }
+        None => move_to::Collision::Suffix,
+    };
+    if input.arguments.contains_key(&a_move_to) && input.flags.contains(&f_trash) {
+        println!("error: --move-to and --trash can't be used together");
+        return;
+    }
+    if input.arguments.contains_key(&a_rename) && (input.arguments.contains_key(&a_move_to) || input.flags.contains(&f_trash)) {
+        println!("error: --rename can't be used with --move-to or --trash");
+        return;
+    }
+    let rename = match input.arguments.get(&a_rename) {
+        Some(template) => match rename::Rename::parse(template, collision) {
+            Some(rename) => Some(rename),
+            None => {
+                println!("error: --rename must have {{name}} in it once, and no /, like {{name}}.deleted or DELETED_{{name}}");
+                return;
+            }
+        },
+        None => None,
+    };
+    let move_to = match input.arguments.get(&a_move_to) {
+        Some(dir) => match move_to::MoveTo::open(PathBuf::from(dir), input.flags.contains(&f_preserve_structure), collision) {
+            Ok(move_to) => Some(move_to),
+            Err(e) => {
+                println!("error: couldn't open the --move-to directory");
+                println!("  {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let until_free = match input.arguments.get(&a_until_free).map(|n| parse_size(n)) {
+        Some(Some(n)) => Some(space::Goal::Bytes(n)),
+        Some(None) => {
+            println!("error: --until-free must be a size");
+            return;
+        }
+        None => None,
+    };
+    let until_free = match input.arguments.get(&a_until_free_inodes).map(|n| n.parse::<u64>()) {
+        Some(Ok(n)) => Some(space::Goal::Inodes(n)),
+        Some(Err(_)) => {
+            println!("error: --until-free-inodes must be a number");
+            return;
+        }
+        None => until_free,
+    };
+    let until_free = match input.arguments.get(&a_until_project_below) {
+        Some(limit) => match limit.split_once(':').and_then(|(id, size)| Some((id.parse::<u32>().ok()?, parse_size(size)?))) {
+            Some((id, below)) => Some(space::Goal::Project { id, below }),
+            None => {
+                println!("error: --until-project-below must be a project id and a size, like 42:10G");
+                return;
+            }
+        },
+        None => until_free,
+    };
+    let quota_aware = input.flags.contains(&f_quota_aware) || input.arguments.contains_key(&a_until_project_below);
+    let normalize = match input.arguments.get(&a_normalize_unicode).map(|n| filter::Normalization::parse(n)) {
+        Some(Some(normalize)) => normalize,
+        Some(None) => {
+            println!("error: --normalize-unicode must be one of nfc, nfd or none");
+            return;
+        }
+        None => filter::Normalization::None,
+    };
+    let mut filters = filter::Filters {
+        regex_full_path: input.flags.contains(&f_regex_full_path),
+        normalize,
+        ignore_case: input.flags.contains(&f_ignore_case),
+        ..Default::default()
+    };
+    let ignore_case = filters.ignore_case;
+    for (argument, name, regex) in [(&a_regex, "--regex", &mut filters.regex), (&a_exclude_regex, "--exclude-regex", &mut filters.exclude_regex)] {
+        if let Some(pattern) = input.arguments.get(argument) {
+            match filter::compile(pattern, normalize, ignore_case) {
+                Ok(r) => *regex = Some(r),
+                Err(e) => {
+                    println!("error: {} isn't a valid regular expression", name);
+                    println!("  {}", e);
+                    return;
+                }
+            }
+        }
+    }
+    for (argument, name, scoped) in [
+        (&a_include_at_depth, "--include-at-depth", &mut filters.include_at_depth),
+        (&a_exclude_at_depth, "--exclude-at-depth", &mut filters.exclude_at_depth),
+    ] {
+        if let Some(value) = input.arguments.get(argument) {
+            let Some((depth, pattern)) = value.split_once(':').and_then(|(d, p)| Some((d.parse::<usize>().ok()?, p))) else {
+                println!("error: {} must be a depth and a pattern, like 1:\\.log$", name);
+                return;
+            };
+            match filter::compile(pattern, normalize, ignore_case) {
+                Ok(r) => *scoped = Some((depth, r)),
+                Err(e) => {
+                    println!("error: {} isn't a valid regular expression", name);
+                    println!("  {}", e);
+                    return;
+                }
+            }
+        }
+    }
+    let placeholder = match input.arguments.get(&a_placeholder_content) {
+        Some(content) => Some(content.clone().into_bytes()),
+        None => input.flags.contains(&f_replace_with_placeholder).then(Vec::new),
+    };
+    // the file is still there until the copy is done, so there'd be no room for the placeholder
+    if placeholder.is_some() && input.flags.contains(&f_trash_async) {
+        println!("error: --replace-with-placeholder can't be used with --trash-move-across-devices-async");
+        return;
+    }
+    let sync_every = match input.arguments.get(&a_sync_every).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            println!("error: --sync-every must be a number above 0");
+            return;
+        }
+        None => None,
+    };
+    let keep_structure = match input.arguments.get(&a_keep_structure_file).map(std::fs::read) {
+        Some(Ok(contents)) => Some(keep_structure(Path::new(&path), &contents)),
+        Some(Err(e)) => {
+            println!("error: couldn't read the --keep-structure-file");
+            println!("  {}", e);
+            return;
+        }
+        None => None,
+    };
+    let order = match input.arguments.get(&a_order).map(|o| space::Order::parse(o)) {
+        Some(Some(order)) => order,
+        Some(None) => {
+            println!("error: --order must be one of oldest, newest, largest or lru");
+            return;
+        }
+        None => space::Order::Oldest,
+    };
+    if input.arguments.contains_key(&a_trash_dir) && !input.flags.contains(&f_trash) && !input.flags.contains(&f_restore) && !input.flags.contains(&f_restore_latest) {
+        println!("error: --trash-dir only makes sense with --trash or --restore");
+        return;
+    }
+    let trash_async_jobs = match input.arguments.get(&a_trash_async_jobs).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            println!("error: --trash-async-jobs must be a number above 0");
+            return;
+        }
+        None => 4,
+    };
+    let restoring = input.flags.contains(&f_restore) || input.flags.contains(&f_restore_latest);
+    let trash = if input.flags.contains(&f_trash) || restoring {
+        let dir = match input.arguments.get(&a_trash_dir) {
+            Some(dir) => {
+                let dir = PathBuf::from(dir);
+                if let Err(e) = trash::check_trash_dir(&dir) {
+                    println!("error: can't use --trash-dir");
+                    println!("  {}", e);
+                    return;
+                }
+                // the paths in .trashinfo files are absolute, so keep the trash's absolute too
+                match dir.canonicalize() {
+                    Ok(dir) => dir,
+                    Err(e) => {
+                        println!("error: can't use --trash-dir");
+                        println!("  {}", e);
+                        return;
+                    }
+                }
+            }
+            None => match trash::home_trash_dir() {
+                Some(dir) => dir,
+                None => {
+                    println!("error: couldn't find the trash directory");
+                    return;
+                }
+            },
+        };
+        match trash::Trash::open(dir, trash_max_size, input.flags.contains(&f_trash_purge_oldest)) {
+            Ok(mut trash) => {
+                trash.extra = input.flags.contains(&f_trash_info_extra);
+                trash.versioned = input.flags.contains(&f_trash_versioned);
+                trash.skip_if_newer = input.flags.contains(&f_skip_if_newer_in_trash);
+                #[cfg(feature = "compress")]
+                {
+                    trash.compress = input.flags.contains(&f_trash_compress);
+                }
+                trash.reflink = reflink;
+                trash.fallback = trash_fallback;
+                if input.flags.contains(&f_trash_async) {
+                    trash.copier = Some(copier::Copier::new(trash_async_jobs));
+                }
+                Some(trash)
+            }
+            Err(e) => {
+                println!("error: couldn't open the trash");
+                println!("  {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
 
-    if dryrun {
+    let count_only = input.flags.contains(&f_count_only) || input.flags.contains(&f_count);
+    if dryrun && !count_only {
         println!("(dryrun) will not delete anything");
     }
 
@@ -260,18 +1040,119 @@ Licensed under Apache 2.0", version).as_str(),
     };
     let path = path.to_str().unwrap();
 
-    let delete_options = DeleteOptions {
+    if let Some(trash) = trash.as_ref().filter(|_| restoring) {
+        restore(trash, Path::new(path), &input.inputs[0], input.flags.contains(&f_restore_latest));
+        return;
+    }
+
+    let mut delete_options = DeleteOptions {
         recursive,
-        umount,
+        mountpoints,
+        unmount_failure,
         dryrun,
         allow_delete_above_start,
+        strict_canonicalize: input.flags.contains(&f_strict_canonicalize),
         enter_symlinks,
         verbose,
-        allow_hidden_files,
+        dotfiles,
         remove_symlinks,
+        dir_summary,
+        trash,
+        backup,
+        move_to,
+        rename,
+        exclude_newer_than_start,
+        age,
+        not_accessed_since,
+        filters,
+        min_depth,
+        no_descend_mountpoints: input.flags.contains(&f_no_descend_mountpoints),
+        accurate_size: input.flags.contains(&f_accurate_size) || input.flags.contains(&f_detect_sparse),
+        with_targets: input.flags.contains(&f_with_targets),
+        two_phase: input.flags.contains(&f_two_phase),
+        confirm_each_dir: input.flags.contains(&f_confirm_each_dir) && !input.flags.contains(&f_yes),
+        deadline,
+        prune_broken_symlinks: input.flags.contains(&f_prune_broken_symlinks),
+        preserve_top: input.flags.contains(&f_preserve_top) || input.flags.contains(&f_keep_root) || tmpfiles,
+        preserve_newest: input.flags.contains(&f_preserve_newest),
+        walk_order,
+        owner,
+        group,
+        path_filter: input.arguments.get(&a_path_filter).map(|c| path_filter::PathFilter::new(c.clone())),
+        // this goes through every process's fds, so only when asked
+        open_files: input.flags.contains(&f_skip_open).then(open_files::OpenFiles::scan),
+        until_free,
+        quota_aware,
+        dedupe,
+        report: report_format.is_some()
+            || input.flags.contains(&f_summary_only_on_change)
+            || input.flags.contains(&f_summary_exit_code_from_freed_bytes)
+            || jsonl.is_some(),
+        report_largest,
+        hardlink_report: input.flags.contains(&f_dangling_hardlink_report),
         starting_dir: Path::new(&path).to_path_buf(),
+        start_time,
+        mount_points: None,
+        bind_mounts: None,
+        pseudo_filesystems: pseudo_fs::mounted(),
+        checkpoint: None,
+        hash_manifest: None,
+        selected: None,
+        placeholder,
+        #[cfg(target_os = "linux")]
+        clear_attrs: input.flags.contains(&f_clear_attrs),
+        #[cfg(not(target_os = "linux"))]
+        clear_attrs: false,
+        sync_every,
+        keep_structure,
+        device_files_dev: None,
+        dir_owner: None,
+        show_ids: input.flags.contains(&f_show_ids),
+        owned_and_writable: input.flags.contains(&f_only_if_owned_and_writable).then(|| unsafe { libc::geteuid() }),
+        devices,
+        abort_on_symlink_escape: None,
+        trace_decisions: input.flags.contains(&f_trace_decisions),
+        parallel_stat: input.flags.contains(&f_parallel_stat),
+        #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+        simulate_errors: None,
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        io_uring: None,
     };
 
+    #[cfg(any(debug_assertions, feature = "simulate-errors"))]
+    match input.arguments.get(&a_simulate_error).map(|f| f.parse::<f64>()) {
+        Some(Ok(fraction)) if (0.0..=1.0).contains(&fraction) => {
+            delete_options.simulate_errors = Some(simulate::SimulatedErrors::new(fraction));
+        }
+        Some(_) => {
+            println!("error: --simulate-error must be a fraction between 0 and 1");
+            return;
+        }
+        None => {}
+    }
+
+    // from here on the run is about where the plan started, not the plan file
+    let applying = if input.flags.contains(&f_apply_plan) {
+        match plan_file::read(&delete_options.starting_dir) {
+            Ok((start, planned)) => {
+                delete_options.starting_dir = start;
+                Some(planned)
+            }
+            Err(e) => {
+                println!("error: couldn't read the plan");
+                println!("  {}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    if delete_options.fast_path_allowed() {
+        delete_options.mount_points = mount_points();
+        log::info!("the fast path is allowed, found {} mount points", delete_options.mount_points.as_ref().map_or(0, |m| m.len()));
+    }
+
     // assert that the path is valid
     let exists = delete_options.starting_dir.try_exists();
     if exists.is_err() {
@@ -281,8 +1162,626 @@ Licensed under Apache 2.0", version).as_str(),
     }
     if !exists.unwrap() {
         println!("error: path does not exist");
+        if input.flags.contains(&f_parents_must_exist) {
+            suggest::suggest(&delete_options.starting_dir);
+        }
         return;
     }
 
-    let result = delete(path, &delete_options);
+    if input.flags.contains(&f_abort_on_symlink_escape) {
+        match delete_options.starting_dir.canonicalize() {
+            Ok(start) => delete_options.abort_on_symlink_escape = Some(start),
+            Err(e) => {
+                println!("error: couldn't resolve the starting path");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    // bind mounts keep the device of what they were bound from, so look them up by path
+    if recursive && !follow_bind_mounts {
+        match delete_options.starting_dir.canonicalize() {
+            Ok(start) => {
+                delete_options.bind_mounts = mount_points_under(&delete_options.starting_dir, &start);
+                if delete_options.bind_mounts.is_none() {
+                    println!("warning: couldn't read the mount table, bind mounts of the same filesystem won't be noticed");
+                }
+            }
+            Err(e) => {
+                println!("error: couldn't resolve the starting path");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    // root can delete anyone's files, so keep to the ones belonging to whoever owns the path
+    let cross_user_guard = input.flags.contains(&f_no_cross_user_dirs) || unsafe { libc::geteuid() } == 0;
+    if recursive && cross_user_guard && !input.flags.contains(&f_allow_cross_user) {
+        match std::fs::metadata(&delete_options.starting_dir) {
+            Ok(metadata) => delete_options.dir_owner = Some(std::os::unix::fs::MetadataExt::uid(&metadata)),
+            Err(e) => {
+                println!("error: couldn't look at the starting path");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    if input.flags.contains(&f_skip_device_files) {
+        match std::fs::symlink_metadata(&delete_options.starting_dir) {
+            Ok(metadata) => delete_options.device_files_dev = Some(std::os::unix::fs::MetadataExt::dev(&metadata)),
+            Err(e) => {
+                println!("error: couldn't look at the starting path");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    if input.flags.contains(&f_selftest) {
+        match selftest::selftest(&delete_options.starting_dir) {
+            Ok(0) => return,
+            Ok(_) => std::process::exit(6),
+            Err(e) => {
+                println!("error: couldn't run the self test");
+                println!("  {}", e);
+                std::process::exit(6);
+            }
+        }
+    }
+
+    // not having it only makes things slower, so it's not worth stopping over
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if input.flags.contains(&f_io_uring) {
+        match uring::Ring::new(uring::ENTRIES) {
+            Ok(ring) => delete_options.io_uring = Some(std::cell::RefCell::new(ring)),
+            Err(e) => println!("warning: couldn't set up io_uring, unlinking files one at a time: {}", e),
+        }
+    }
+
+    if input.flags.contains(&f_bench) {
+        // these would put copies of the tree somewhere outside of it
+        if delete_options.trash.is_some() || delete_options.backup.is_some() || delete_options.move_to.is_some() {
+            println!("error: --bench can't be used with --trash, --backup or --move-to");
+            return;
+        }
+        let mut shape = bench::Shape { width: 10, depth: 3, file_size: 0 };
+        for (argument, name, value) in [(&a_bench_width, "--bench-width", &mut shape.width), (&a_bench_depth, "--bench-depth", &mut shape.depth)] {
+            match input.arguments.get(argument).map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) => *value = n,
+                Some(Err(_)) => {
+                    println!("error: {} must be a number", name);
+                    return;
+                }
+                None => {}
+            }
+        }
+        match input.arguments.get(&a_bench_file_size).map(|s| parse_size(s)) {
+            Some(Some(size)) => shape.file_size = size,
+            Some(None) => {
+                println!("error: --bench-file-size must be a size");
+                return;
+            }
+            None => {}
+        }
+        if let Err(e) = bench::bench(&delete_options.starting_dir.clone(), &shape, &mut delete_options) {
+            println!("error: couldn't run the benchmark");
+            println!("  {}", e);
+            std::process::exit(6);
+        }
+        return;
+    }
+
+    // we'd end up walking into the files we just moved
+    if let Some(move_to) = &delete_options.move_to {
+        if let (Ok(dir), Ok(start)) = (move_to.dir.canonicalize(), delete_options.starting_dir.canonicalize()) {
+            if dir.starts_with(&start) {
+                println!("error: the --move-to directory can't be inside what's being deleted");
+                return;
+            }
+        }
+    }
+
+    if input.flags.contains(&f_gitignore) {
+        match gitignore::GitIgnore::open(&delete_options.starting_dir, input.flags.contains(&f_force_gitignore)) {
+            Ok(gitignore) => delete_options.filters.gitignore = Some(gitignore),
+            Err(e) => {
+                println!("error: can't use --gitignore");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    if input.flags.contains(&f_refuse_if_git_tracked) && !input.flags.contains(&f_allow_tracked) {
+        match gitignore::tracked_under(&delete_options.starting_dir) {
+            Ok(Some(tracked)) => delete_options.filters.git_tracked = Some(tracked),
+            Ok(None) => log::info!("{} isn't in a git repository, nothing is tracked", delete_options.starting_dir.display()),
+            // inside a repository, going ahead without knowing would be what this is there to stop
+            Err(e) => {
+                println!("error: couldn't find out what git tracks");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    // nothing but the number goes to stdout, so it can be used straight from a script
+    if count_only {
+        // which of the files go is only picked once they've all been found, so the plan can't say
+        if delete_options.hold_back() {
+            println!("error: --count-only can't be used with --until-free, --until-free-inodes, --until-project-below or --dedupe");
+            return;
+        }
+        let count = plan::plan(&delete_options.starting_dir, &delete_options)
+            .filter(|action| matches!(action, plan::PlannedAction::Delete(_)))
+            .count();
+        println!("{}", count);
+        return;
+    }
+
+    if input.flags.contains(&f_tree) {
+        let color = match input.arguments.get(&a_color).map(|c| tree::Color::parse(c)) {
+            Some(Some(color)) => color,
+            Some(None) => {
+                println!("error: --color must be one of auto, always or never");
+                return;
+            }
+            None => tree::Color::Auto,
+        };
+        let thresholds = match input.arguments.get(&a_heatmap_thresholds) {
+            Some(t) => match t.split_once(':').and_then(|(small, large)| Some((parse_size(small)?, parse_size(large)?))) {
+                Some((small, large)) if small <= large => Some(tree::Heatmap { small, large }),
+                _ => {
+                    println!("error: --heatmap-thresholds must be two sizes, the smaller first, like 1M:100M");
+                    return;
+                }
+            },
+            None => None,
+        };
+        let heatmap = Some(thresholds).filter(|_| input.flags.contains(&f_heatmap) && color.enabled());
+        tree::print(&delete_options.starting_dir, &delete_options, heatmap);
+        return;
+    }
+    if let Some(shown) = input.arguments.get(&a_preview) {
+        let Ok(shown) = shown.parse::<usize>() else {
+            println!("error: --preview must be a number");
+            return;
+        };
+        if !dryrun {
+            println!("error: --preview only works with --dryrun");
+            return;
+        }
+        preview::Preview::sample(&delete_options.starting_dir, &delete_options, shown).print();
+        return;
+    }
+    if input.flags.contains(&f_heatmap) || input.arguments.contains_key(&a_heatmap_thresholds) {
+        println!("error: --heatmap and --heatmap-thresholds only work with --tree");
+        return;
+    }
+
+    if let Some(file) = input.arguments.get(&a_plan_file) {
+        match plan_file::write(Path::new(file), &delete_options.starting_dir, &delete_options) {
+            Ok(count) => println!("wrote a plan of {} things to do to {}, run it with --apply-plan {}", count, file, file),
+            Err(e) => {
+                println!("error: couldn't write the plan");
+                println!("  {}", e);
+                std::process::exit(6);
+            }
+        }
+        return;
+    }
+
+    // the first run saves what it would delete, later ones compare against that
+    if let Some(file) = input.arguments.get(&a_dry_run_diff) {
+        let file = Path::new(file);
+        let planned = diff::planned(&delete_options.starting_dir, &delete_options);
+        match diff::load(file) {
+            Ok(Some(saved)) => diff::print(file, &saved, &planned),
+            Ok(None) => match diff::save(file, &planned) {
+                Ok(()) => println!("saved the {} paths this would delete to {}, run again to compare", planned.len(), file.display()),
+                Err(e) => {
+                    println!("error: couldn't save the plan");
+                    println!("  {}", e);
+                    std::process::exit(6);
+                }
+            },
+            Err(e) => {
+                println!("error: couldn't read the saved plan");
+                println!("  {}", e);
+                std::process::exit(6);
+            }
+        }
+        return;
+    }
+
+    if let Some(file) = input.arguments.get(&a_checkpoint) {
+        // a dry run or a held back --until-free run hasn't actually finished anything
+        if dryrun || delete_options.hold_back() {
+            println!("error: --checkpoint can't be used with --dryrun, --until-free or --dedupe");
+            return;
+        }
+        match checkpoint::Checkpoint::open(PathBuf::from(file), &delete_options.starting_dir) {
+            Ok(checkpoint) => delete_options.checkpoint = Some(checkpoint),
+            Err(e) => {
+                println!("error: couldn't read the checkpoint");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    if let Some(file) = input.arguments.get(&a_hash_manifest) {
+        match manifest::Manifest::create(Path::new(file)) {
+            Ok(manifest) => delete_options.hash_manifest = Some(manifest),
+            Err(e) => {
+                println!("error: couldn't make the --hash-manifest file");
+                println!("  {}", e);
+                return;
+            }
+        }
+    }
+
+    if input.flags.contains(&f_permissions_report) {
+        if permissions::report(&delete_options.starting_dir, recursive) > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // held until we exit, which has to go through exit() so it's let go of
+    let lock = if input.flags.contains(&f_no_lock) {
+        None
+    } else {
+        let canonical = delete_options.starting_dir.canonicalize().unwrap_or_else(|_| delete_options.starting_dir.clone());
+        match lock::Lock::acquire(&canonical) {
+            Ok(lock) => Some(lock),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                println!("error: another saferm is operating on {}", canonical.display());
+                std::process::exit(2);
+            }
+            Err(e) => {
+                println!("error: couldn't take the lock for {}", canonical.display());
+                println!("  {}", e);
+                std::process::exit(2);
+            }
+        }
+    };
+
+    // meant for cron jobs and the like, so there's no asking, only stopping, and it comes
+    // before the prompts so an unattended run is refused rather than left waiting on one
+    if let Some(limit) = input.arguments.get(&a_abort_if_above).filter(|_| !dryrun && !input.flags.contains(&f_yes)) {
+        let Ok(limit) = limit.parse::<usize>() else {
+            println!("error: --abort-if-above must be a number");
+            return;
+        };
+        let count = plan::plan(&delete_options.starting_dir, &delete_options)
+            .filter(|action| matches!(action, plan::PlannedAction::Delete(_)))
+            .count();
+        if count > limit {
+            println!("refusing to delete {} files (limit {}); pass --yes or raise --abort-if-above", count, limit);
+            exit(lock, 2);
+        }
+    }
+
+    if input.flags.contains(&f_confirm_path) && !input.flags.contains(&f_yes) {
+        let canonical = match delete_options.starting_dir.canonicalize() {
+            Ok(p) => p,
+            Err(e) => {
+                println!("error: path is invalid");
+                println!("  {}", e);
+                return;
+            }
+        };
+        if !confirm_path(&canonical) {
+            println!("path didn't match, aborting");
+            return;
+        }
+    }
+
+    // if we were told roughly how many files to expect, make sure that's what we're about to delete
+    if let Some(expected) = expect_count {
+        if recursive {
+            // going by the plan, so it's what the run would actually delete and not just what's there
+            let actual = plan::plan(&delete_options.starting_dir, &delete_options)
+                .filter(|action| matches!(action, plan::PlannedAction::Delete(_)))
+                .count();
+            let difference = (actual as f64 - expected as f64).abs() / (expected.max(1) as f64) * 100.0;
+            if difference > expect_tolerance {
+                println!("expected {} files but found {} ({:.0}% difference)", expected, actual, difference);
+                if !confirm("proceed anyway?") {
+                    println!("aborting");
+                    return;
+                }
+            }
+        }
+    }
+
+    if input.flags.contains(&f_prompt_summary_before) && !input.flags.contains(&f_yes) {
+        preview::Preview::gather(&delete_options.starting_dir, &delete_options).print();
+        if !confirm("proceed?") {
+            println!("aborting");
+            exit(lock, 2);
+        }
+    }
+
+    let swap_empty = input.flags.contains(&f_swap_empty);
+    if input.flags.contains(&f_interactive_filter_repl) {
+        if swap_empty || input.flags.contains(&f_interactive_editor) {
+            println!("error: --interactive-filter-repl can't be used with --swap-empty or --interactive-editor");
+            exit(lock, 2);
+        }
+        match repl::refine_plan(&delete_options.starting_dir, &delete_options) {
+            Ok(Some(selected)) if selected.is_empty() => {
+                println!("nothing left in the plan, not deleting anything");
+                return;
+            }
+            Ok(Some(selected)) => delete_options.selected = Some(selected),
+            Ok(None) => {
+                println!("not deleting anything");
+                exit(lock, 2);
+            }
+            Err(e) => {
+                println!("error: couldn't read from the prompt");
+                println!("  {}", e);
+                exit(lock, 2);
+            }
+        }
+    }
+    if input.flags.contains(&f_interactive_editor) {
+        // the list is of paths in the starting directory, which would be moved aside
+        if swap_empty {
+            println!("error: --interactive-editor can't be used with --swap-empty");
+            exit(lock, 2);
+        }
+        match review::edit_plan(&delete_options.starting_dir, &delete_options) {
+            Ok(Some(selected)) if selected.is_empty() => {
+                println!("nothing left in the list, not deleting anything");
+                return;
+            }
+            Ok(Some(selected)) => delete_options.selected = Some(selected),
+            Ok(None) => {
+                println!("the editor exited with an error, not deleting anything");
+                exit(lock, 2);
+            }
+            Err(e) => {
+                println!("error: couldn't edit the list of what to delete");
+                println!("  {}", e);
+                exit(lock, 2);
+            }
+        }
+    }
+
+    let mut state = RunState { jsonl, max_errors, ..Default::default() };
+    if let Some(dir) = input.arguments.get(&a_partition_by_error) {
+        let separator = if input.flags.contains(&f_null) { b'\0' } else { b'\n' };
+        match partition::Partition::create(Path::new(dir), separator) {
+            Ok(partition) => state.partition = Some(partition),
+            Err(e) => {
+                println!("error: couldn't make the --partition-by-error files");
+                println!("  {}", e);
+                exit(lock, 6);
+            }
+        }
+    }
+    #[cfg(all(unix, feature = "syslog"))]
+    {
+        state.syslog = syslog;
+    }
+    if swap_empty {
+        // these all record where a file was, which would be the aside directory
+        if delete_options.trash.is_some() || delete_options.backup.is_some() || delete_options.move_to.is_some() || delete_options.checkpoint.is_some() {
+            println!("error: --swap-empty can't be used with --trash, --backup, --move-to or --checkpoint");
+            exit(lock, 2);
+        }
+        if !recursive || dryrun {
+            println!("error: --swap-empty needs --recursive and can't be used with --dryrun");
+            exit(lock, 2);
+        }
+    }
+    let swapped = if swap_empty && delete_options.starting_dir.is_dir() && !delete_options.starting_dir.is_symlink() {
+        match swap::swap_empty(&delete_options.starting_dir) {
+            Ok(aside) => Some(aside),
+            Err(e) => {
+                println!("couldn't swap {} for an empty directory, deleting in place", delete_options.starting_dir.display());
+                println!("  {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    match &swapped {
+        Some(aside) => {
+            if verbose { println!("swapped {} for an empty directory, deleting the old one from {}", path, aside.display()); }
+            let start = std::mem::replace(&mut delete_options.starting_dir, aside.clone());
+            delete(&aside.to_string_lossy(), &delete_options, &mut state);
+            delete_options.starting_dir = start;
+            if let Err(e) = swap::restore_leftovers(aside, &delete_options.starting_dir) {
+                println!("error moving what was left in {} back: {}", aside.display(), e);
+                state.record_error(&e);
+            }
+        }
+        None => match &applying {
+            Some(planned) => plan_file::apply(planned, &delete_options, &mut state),
+            None => delete(path, &delete_options, &mut state),
+        },
+    }
+    // whatever was removed since the last batch
+    if delete_options.sync_every.is_some() {
+        state.sync();
+    }
+    if let Some(checkpoint) = &delete_options.checkpoint {
+        // once everything's gone there's nothing to resume
+        let result = if delete_options.starting_dir.symlink_metadata().is_err() {
+            std::fs::remove_file(&checkpoint.path).or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+        } else {
+            checkpoint.save()
+        };
+        if let Err(e) = result {
+            println!("warning: couldn't write the checkpoint {}: {}", checkpoint.path.display(), e);
+        }
+    }
+    if let Some(goal) = delete_options.until_free.filter(|_| !state.stopped) {
+        space::delete_until_free(goal, order, &delete_options, &mut state);
+    }
+    if delete_options.dedupe && !state.stopped {
+        dedupe::delete_duplicates(keep, &delete_options, &mut state);
+    }
+    if delete_options.with_targets && !state.stopped {
+        targets::delete_orphaned_targets(&delete_options, &mut state);
+    }
+    wait_for_copies(&delete_options, &mut state);
+    if delete_options.two_phase && !dryrun {
+        verify::verify_removed(&delete_options, &mut state);
+    }
+    if !state.failed_copies.is_empty() {
+        println!("{} files couldn't be copied into the trash and were left where they were:", state.failed_copies.len());
+        for path in &state.failed_copies {
+            println!("  {}", path.display());
+        }
+    }
+    if !state.largest.is_empty() {
+        let largest: Vec<_> = state.largest().iter().map(|(size, path)| format!("{} {}", format_size(*size), path.display())).collect();
+        let removed = if dryrun { "largest that would be removed" } else { "largest removed" };
+        println!("{}: {}", removed, largest.join(", "));
+    }
+    if delete_options.hardlink_report {
+        let (kept, freed): (Vec<_>, Vec<_>) = state.hardlinks.values().partition(|h| h.remaining() > 0);
+        if !freed.is_empty() {
+            let size: u64 = freed.iter().map(|h| h.size).sum();
+            println!("{} hardlinked files had every name deleted, freeing {}", freed.len(), format_size(size));
+        }
+        if !kept.is_empty() {
+            let size: u64 = kept.iter().map(|h| h.size).sum();
+            println!("{} deleted files still have names elsewhere, so {} wasn't freed:", kept.len(), format_size(size));
+            for hardlinked in kept {
+                let left = if hardlinked.remaining() == 1 { "1 link left".to_string() } else { format!("{} links left", hardlinked.remaining()) };
+                for path in &hardlinked.paths {
+                    println!("  {} ({})", path.display(), left);
+                }
+            }
+        }
+    }
+    if quota_aware && !state.projects.is_empty() {
+        let freed = if dryrun { "would be freed in each quota project" } else { "freed in each quota project" };
+        println!("{}:", freed);
+        for (project, bytes) in &state.projects {
+            // only root can ask how much a project uses
+            match quota::usage(&delete_options.starting_dir, *project) {
+                Ok(usage) => println!("  project {}: {}, now uses {}", project, format_size(*bytes), format_size(usage)),
+                Err(_) => println!("  project {}: {}", project, format_size(*bytes)),
+            }
+        }
+    }
+    if !state.skipped_owners.is_empty() {
+        println!("skipped because of --owner:");
+        for (uid, count) in &state.skipped_owners {
+            println!("  {} owned by {}", count, owner::user_name(*uid));
+        }
+    }
+    if let Some(&tracked) = state.skipped.get("tracked").filter(|_| delete_options.filters.git_tracked.is_some()) {
+        let (files, them) = if tracked == 1 { ("1 file".to_string(), "it") } else { (format!("{} files", tracked), "them") };
+        println!("kept {} because git tracks {}, --allow-tracked deletes {}", files, them, them);
+    }
+    if !state.skipped_devices.is_empty() {
+        println!("skipped because of --device-whitelist and --device-blacklist:");
+        for (dev, count) in &state.skipped_devices {
+            match devices::mount_point_of(*dev) {
+                Some(mount) => println!("  {} on {} ({})", count, devices::format_dev(*dev), mount.display()),
+                None => println!("  {} on {}", count, devices::format_dev(*dev)),
+            }
+        }
+    }
+    if !state.skipped_groups.is_empty() {
+        println!("skipped because of --group:");
+        for (gid, count) in &state.skipped_groups {
+            println!("  {} in group {}", count, owner::group_name(*gid));
+        }
+    }
+    // json and csv have the counts by reason in them already, for whatever reads them to chart
+    if input.flags.contains(&f_skip_histogram) && matches!(report_format, None | Some(summary::Format::Text)) {
+        print!("{}", summary::histogram(&state.skipped));
+    }
+    let report_format = if input.flags.contains(&f_summary_only_on_change) {
+        // a run that found nothing to do doesn't need to tell anyone
+        let changed = state.files_deleted + state.dirs_removed + state.errors > 0;
+        changed.then_some(report_format.unwrap_or(summary::Format::Text))
+    } else {
+        report_format
+    };
+    if let Some(format) = report_format {
+        let elapsed = start_time.elapsed().unwrap_or_default();
+        println!("{}", summary::Summary::new(&state, elapsed).format(format));
+    }
+    if let Some(manifest) = delete_options.hash_manifest.take() {
+        if let Err(e) = manifest.finish() {
+            println!("error: couldn't write the --hash-manifest file");
+            println!("  {}", e);
+            state.record_error(&e);
+        }
+    }
+    if let Some(partition) = state.partition.take() {
+        if let Err(e) = partition.finish() {
+            println!("error: couldn't write the --partition-by-error files");
+            println!("  {}", e);
+            state.record_error(&e);
+        }
+    }
+    #[cfg(all(unix, feature = "syslog"))]
+    if let Some(syslog) = &state.syslog {
+        let elapsed = start_time.elapsed().unwrap_or_default();
+        let summary = summary::Summary::new(&state, elapsed).text();
+        syslog.send(&format!("{}: {}", delete_options.starting_dir.display(), summary.replace('\n', ", ")));
+    }
+    let mut left_behind = false;
+    if input.flags.contains(&f_verify_empty) && delete_options.starting_dir.symlink_metadata().is_ok() {
+        let leftovers = if delete_options.starting_dir.is_dir() && !delete_options.starting_dir.is_symlink() {
+            leftovers(&delete_options.starting_dir)
+        } else {
+            vec![delete_options.starting_dir.clone()]
+        };
+        if !leftovers.is_empty() {
+            println!("error: {} isn't empty, {} left behind:", delete_options.starting_dir.display(), leftovers.len());
+            for leftover in leftovers.iter().take(VERIFY_EMPTY_SHOWN) {
+                println!("  {}", leftover.display());
+            }
+            if leftovers.len() > VERIFY_EMPTY_SHOWN {
+                println!("  and {} more", leftovers.len() - VERIFY_EMPTY_SHOWN);
+            }
+            left_behind = true;
+        }
+    }
+    if state.escaped {
+        exit(lock, 9);
+    }
+    if state.aborted {
+        exit(lock, 2);
+    }
+    if state.stopped {
+        // with --report-format this was already printed
+        if report_format.is_none() {
+            let elapsed = start_time.elapsed().unwrap_or_default();
+            println!("{}", summary::Summary::new(&state, elapsed).text());
+        }
+        exit(lock, if state.tripped { 10 } else { 8 });
+    }
+    if let Some(worst_error) = state.worst_error {
+        exit(lock, worst_error.exit_code());
+    }
+    // any errors say more about why something was left behind, so they win
+    if left_behind {
+        exit(lock, 7);
+    }
+    if input.flags.contains(&f_summary_exit_code_from_freed_bytes) {
+        exit(lock, summary::freed_exit_code(state.bytes_freed));
+    }
+}
+
+/// lets go of the run's lock and exits, `std::process::exit` doesn't run destructors
+fn exit(lock: Option<lock::Lock>, code: i32) -> ! {
+    drop(lock);
+    std::process::exit(code)
 }
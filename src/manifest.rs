@@ -0,0 +1,45 @@
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::prelude::*;
+use std::path::Path;
+use crate::dirfd::Dir;
+
+/// a record of what was deleted and the sha256 of what was in it, written as it happens, for
+/// `--hash-manifest`. each line is the hash, the size and the path with tabs in between, the
+/// path goes last so a tab in it doesn't get in the way of splitting them
+pub struct Manifest {
+    file: RefCell<BufWriter<File>>,
+    /// the first thing that went wrong writing, the rest are likely the same
+    failed: RefCell<Option<io::Error>>,
+}
+
+impl Manifest {
+    pub fn create(path: &Path) -> io::Result<Manifest> {
+        Ok(Manifest { file: RefCell::new(BufWriter::new(File::create(path)?)), failed: RefCell::default() })
+    }
+
+    /// reads the file `name` in `dir` through to hash it, this has to happen before it's gone
+    pub fn hash(dir: &Dir, name: &OsStr) -> io::Result<[u8; 32]> {
+        crate::dedupe::hash(dir.open_child_file(name)?)
+    }
+
+    pub fn record(&self, path: &Path, size: u64, hash: &[u8; 32]) {
+        let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        let mut file = self.file.borrow_mut();
+        let result = file.write_all(format!("{}\t{}\t", hex, size).as_bytes())
+            .and_then(|_| file.write_all(path.as_os_str().as_bytes()))
+            .and_then(|_| file.write_all(b"\n"));
+        let mut failed = self.failed.borrow_mut();
+        *failed = failed.take().or(result.err());
+    }
+
+    /// writes out whatever is still buffered, returning the first error from any of it
+    pub fn finish(self) -> io::Result<()> {
+        if let Some(e) = self.failed.take() {
+            return Err(e);
+        }
+        self.file.into_inner().flush()
+    }
+}
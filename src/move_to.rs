@@ -0,0 +1,73 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::backup::{keep_structure, unclaimed};
+use crate::dirfd::{Dir, Stat};
+
+/// what to do when something is already where a file would be moved to
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Collision {
+    /// move it to the first free `name.N` instead
+    Suffix,
+    /// leave the file where it is
+    Skip,
+}
+
+impl Collision {
+    pub fn parse(collision: &str) -> Option<Collision> {
+        match collision {
+            "suffix" => Some(Collision::Suffix),
+            "skip" => Some(Collision::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// a directory that files are moved into instead of being deleted
+pub struct MoveTo {
+    pub dir: PathBuf,
+    /// keep paths relative to the parent of where we started instead of putting everything in `dir`
+    pub preserve_structure: bool,
+    pub collision: Collision,
+}
+
+impl MoveTo {
+    /// opens (and creates if needed) the directory to move files into
+    pub fn open(dir: PathBuf, preserve_structure: bool, collision: Collision) -> io::Result<MoveTo> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(MoveTo { dir, preserve_structure, collision })
+    }
+
+    /// where a file would go before dealing with collisions
+    fn destination(&self, name: &OsStr, path: &Path, starting_dir: &Path) -> PathBuf {
+        if self.preserve_structure {
+            keep_structure(&self.dir, path, starting_dir)
+        } else {
+            self.dir.join(name)
+        }
+    }
+
+    /// returns true if the file would be skipped because its destination is taken
+    pub fn collides(&self, name: &OsStr, path: &Path, starting_dir: &Path) -> bool {
+        self.collision == Collision::Skip && self.destination(name, path, starting_dir).symlink_metadata().is_ok()
+    }
+
+    /// moves the file `name` in `dir` (which lives at `path`) into the destination directory.
+    /// the original is only gone once the move worked
+    pub fn move_file(&self, dir: &Dir, name: &OsStr, path: &Path, stat: &Stat, starting_dir: &Path) -> io::Result<()> {
+        let destination = self.destination(name, path, starting_dir);
+        let destination = match self.collision {
+            Collision::Suffix => unclaimed(destination),
+            // something could have shown up since we checked, and rename would clobber it
+            Collision::Skip if destination.symlink_metadata().is_ok() => {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", destination.display())));
+            }
+            Collision::Skip => destination,
+        };
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        log::debug!("moving {} to {}", path.display(), destination.display());
+        crate::trash::move_file(dir, name, path, stat, &destination, crate::reflink::Reflink::Never)
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::os::unix::prelude::*;
+use crate::dirfd::Stat;
+
+/// which processes had which files open, found by going through `/proc/*/fd`. this is only a
+/// snapshot from when it was made, so anything opened after that won't be in it
+pub struct OpenFiles {
+    /// pids by device and inode
+    holders: HashMap<(u64, u64), u32>,
+}
+
+impl OpenFiles {
+    /// looks at the open fds of every process we're allowed to look at, other than us
+    pub fn scan() -> OpenFiles {
+        let mut holders = HashMap::new();
+        let own_pid = std::process::id();
+        let processes = match std::fs::read_dir("/proc") {
+            Ok(processes) => processes,
+            Err(_) => return OpenFiles { holders },
+        };
+        for process in processes.flatten() {
+            let pid = match process.file_name().to_str().and_then(|p| p.parse::<u32>().ok()) {
+                Some(pid) if pid != own_pid => pid,
+                _ => continue,
+            };
+            // processes belonging to other users can't be looked at, and processes can go away
+            let fds = match std::fs::read_dir(process.path().join("fd")) {
+                Ok(fds) => fds,
+                Err(_) => continue,
+            };
+            for fd in fds.flatten() {
+                // following the fd's symlink gets us what's actually open, even if it was renamed
+                if let Ok(metadata) = std::fs::metadata(fd.path()) {
+                    if metadata.is_file() {
+                        holders.entry((metadata.dev(), metadata.ino())).or_insert(pid);
+                    }
+                }
+            }
+        }
+        log::info!("found {} open files in /proc", holders.len());
+        OpenFiles { holders }
+    }
+
+    /// returns the pid of a process that has a regular file open, if any does
+    pub fn holder(&self, stat: &Stat) -> Option<u32> {
+        if !stat.is_file() {
+            return None;
+        }
+        self.holders.get(&(stat.dev(), stat.ino())).copied()
+    }
+}
@@ -0,0 +1,45 @@
+use std::ffi::{CStr, CString};
+
+/// resolves what was given to `--owner`, either a user name or a uid
+pub fn lookup_user(user: &str) -> Option<u32> {
+    if let Ok(uid) = user.parse() {
+        return Some(uid);
+    }
+    let name = CString::new(user).ok()?;
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        return None;
+    }
+    Some(unsafe { (*passwd).pw_uid })
+}
+
+/// the name of a user, or their uid if they don't have one
+pub fn user_name(uid: u32) -> String {
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }.to_string_lossy().into_owned()
+}
+
+/// resolves what was given to `--group`, either a group name or a gid
+pub fn lookup_group(group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse() {
+        return Some(gid);
+    }
+    let name = CString::new(group).ok()?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        return None;
+    }
+    Some(unsafe { (*entry).gr_gid })
+}
+
+/// the name of a group, or its gid if it doesn't have one
+pub fn group_name(gid: u32) -> String {
+    let entry = unsafe { libc::getgrgid(gid) };
+    if entry.is_null() {
+        return gid.to_string();
+    }
+    unsafe { CStr::from_ptr((*entry).gr_name) }.to_string_lossy().into_owned()
+}
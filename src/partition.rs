@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::prelude::*;
+use std::path::Path;
+use crate::SkipReason;
+
+/// writes what was deleted, what was skipped and what failed to three files in a directory as
+/// it happens, for `--partition-by-error`. the failures can be fed back in for a retry
+pub struct Partition {
+    deleted: BufWriter<File>,
+    skipped: BufWriter<File>,
+    errors: BufWriter<File>,
+    /// what goes after each entry, a newline or a nul for `-0`
+    separator: u8,
+    /// the first thing that went wrong writing, the rest are likely the same
+    failed: Option<io::Error>,
+}
+
+impl Partition {
+    /// makes `deleted.txt`, `skipped.txt` and `errors.txt` in `dir`, which is made if it isn't there
+    pub fn create(dir: &Path, separator: u8) -> io::Result<Partition> {
+        std::fs::create_dir_all(dir)?;
+        let open = |name| File::create(dir.join(name)).map(BufWriter::new);
+        Ok(Partition { deleted: open("deleted.txt")?, skipped: open("skipped.txt")?, errors: open("errors.txt")?, separator, failed: None })
+    }
+
+    pub fn deleted(&mut self, path: &Path) {
+        let result = write_entry(&mut self.deleted, path.as_os_str().as_bytes(), self.separator);
+        self.failed = self.failed.take().or(result.err());
+    }
+
+    /// skips are written as the reason and the path with a tab in between, the reason goes first
+    /// so a tab in the path doesn't get in the way of splitting them
+    pub fn skipped(&mut self, path: &Path, reason: &SkipReason) {
+        let entry = [reason.name().as_bytes(), b"\t", path.as_os_str().as_bytes()].concat();
+        let result = write_entry(&mut self.skipped, &entry, self.separator);
+        self.failed = self.failed.take().or(result.err());
+    }
+
+    pub fn failed(&mut self, path: &Path) {
+        let result = write_entry(&mut self.errors, path.as_os_str().as_bytes(), self.separator);
+        self.failed = self.failed.take().or(result.err());
+    }
+
+    /// writes out whatever is still buffered, returning the first error from any of it
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.failed.take() {
+            return Err(e);
+        }
+        self.deleted.flush()?;
+        self.skipped.flush()?;
+        self.errors.flush()
+    }
+}
+
+fn write_entry(file: &mut BufWriter<File>, entry: &[u8], separator: u8) -> io::Result<()> {
+    file.write_all(entry)?;
+    file.write_all(&[separator])
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// an outside program that gets the final say on each file, for `--path-filter`. it's run once
+/// per file, one at a time, so it adds a process spawn to every file that gets this far
+pub struct PathFilter {
+    /// run by `sh -c`, with `{}` standing for the path, or the path added to the end if it
+    /// has no `{}`
+    pub command: String,
+}
+
+impl PathFilter {
+    pub fn new(command: String) -> PathFilter {
+        PathFilter { command }
+    }
+
+    /// runs the program for `path`, returning true if it exited with 0. anything going wrong
+    /// in the program keeps the file
+    pub fn allows(&self, path: &Path) -> bool {
+        // the path goes in as $1 so the shell never sees it as part of the script
+        let script = if self.command.contains("{}") {
+            self.command.replace("{}", "\"$1\"")
+        } else {
+            format!("{} \"$1\"", self.command)
+        };
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .arg("sh")
+            .arg(path)
+            .stdin(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.code().is_none() => {
+                println!("warning: --path-filter was killed checking {}, keeping it", path.display());
+                false
+            }
+            Ok(status) => status.success(),
+            Err(e) => {
+                println!("warning: couldn't run --path-filter for {}, keeping it: {}", path.display(), e);
+                false
+            }
+        }
+    }
+}
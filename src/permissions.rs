@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// why we wouldn't be able to delete something
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Blocker {
+    /// unlinking needs write permission on the parent directory
+    ParentNotWritable,
+    /// the parent is sticky and the entry belongs to someone else
+    StickyParent,
+    /// we can't list the directory, so we can't empty it
+    NotReadable,
+}
+
+impl Blocker {
+    fn describe(&self) -> &'static str {
+        match self {
+            Blocker::ParentNotWritable => "parent directory not writable",
+            Blocker::StickyParent => "parent directory is sticky and entry is owned by someone else",
+            Blocker::NotReadable => "directory not readable",
+        }
+    }
+}
+
+/// checks access with the effective ids, which is what unlink will actually use
+pub(crate) fn access(path: &Path, mode: libc::c_int) -> bool {
+    let path_cstr = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    unsafe { libc::faccessat(libc::AT_FDCWD, path_cstr.as_ptr(), mode, libc::AT_EACCESS) == 0 }
+}
+
+/// returns what would stop us from deleting a single entry, if anything
+fn check(path: &Path, metadata: &std::fs::Metadata) -> Option<Blocker> {
+    let parent = path.parent()?;
+    if !access(parent, libc::W_OK | libc::X_OK) {
+        return Some(Blocker::ParentNotWritable);
+    }
+    let euid = unsafe { libc::geteuid() };
+    if euid != 0 {
+        if let Ok(parent_metadata) = parent.metadata() {
+            // sticky bit
+            if parent_metadata.mode() & 0o1000 != 0
+                && metadata.uid() != euid
+                && parent_metadata.uid() != euid {
+                return Some(Blocker::StickyParent);
+            }
+        }
+    }
+    if metadata.is_dir() && !access(path, libc::R_OK | libc::X_OK) {
+        return Some(Blocker::NotReadable);
+    }
+    None
+}
+
+fn walk(path: &Path, recursive: bool, blockers: &mut BTreeMap<Blocker, Vec<PathBuf>>) {
+    let metadata = match path.symlink_metadata() {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+    if let Some(blocker) = check(path, &metadata) {
+        blockers.entry(blocker).or_default().push(path.to_path_buf());
+    }
+    if recursive && metadata.is_dir() {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.flatten() {
+                walk(&entry.path(), recursive, blockers);
+            }
+        }
+    }
+}
+
+/// walks the tree and prints everything we couldn't delete, grouped by reason.
+/// returns the number of blocked entries
+pub fn report(path: &Path, recursive: bool) -> usize {
+    let mut blockers = BTreeMap::new();
+    walk(path, recursive, &mut blockers);
+    let mut total = 0;
+    for (blocker, paths) in &blockers {
+        println!("{} ({}):", blocker.describe(), paths.len());
+        for path in paths {
+            println!("  {}", path.display());
+        }
+        total += paths.len();
+    }
+    if total == 0 {
+        println!("no permission problems found");
+    }
+    total
+}
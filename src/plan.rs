@@ -0,0 +1,184 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::dirfd::{Dir, Stat};
+use crate::{decide, newest_file, open_dir, Decision, DeleteOptions, Entry, SkipReason, Start};
+
+/// something a run would do, as decided by the same checks a real run goes through
+#[derive(Debug)]
+pub enum PlannedAction {
+    /// a file or symlink that would be deleted (or trashed)
+    Delete(PathBuf),
+    Skip { path: PathBuf, reason: SkipReason },
+    /// a directory whose contents come next
+    EnterDir(PathBuf),
+    /// a directory that would be removed after its contents, since they'd all be gone
+    RemoveDir(PathBuf),
+    /// a mount point that would be unmounted
+    Unmount(PathBuf),
+    /// something we couldn't look at, a real run would report the same error
+    Error { path: PathBuf, error: io::Error },
+}
+
+/// a directory we're part way through
+struct Frame {
+    dir: Dir,
+    path: PathBuf,
+    dev: u64,
+    names: std::vec::IntoIter<OsString>,
+    /// false once anything in here is going to be left behind
+    removed_all: bool,
+    /// how far below the starting path the entries in here are
+    depth: usize,
+    /// the directory itself
+    stat: Stat,
+    /// the file in here `--preserve-newest-in-each-dir` keeps
+    newest: Option<OsString>,
+}
+
+struct Plan<'a> {
+    options: &'a DeleteOptions,
+    /// the bottom frame is the parent of where we start, holding only the starting path
+    stack: Vec<Frame>,
+    /// what to hand out first, for when we can't even get started
+    first: Option<PlannedAction>,
+}
+
+/// walks `path` the way `delete` would and returns what it would do, in the order it would
+/// do it, without touching anything. directories are walked lazily, as the iterator is used
+pub fn plan<'a>(path: &Path, options: &'a DeleteOptions) -> impl Iterator<Item = PlannedAction> + 'a {
+    let mut plan = Plan { options, stack: Vec::new(), first: None };
+    match Start::open(path) {
+        Ok(Some(start)) => plan.stack.push(Frame {
+            path: path.parent().unwrap_or(path).to_path_buf(),
+            dev: start.parent_dev,
+            names: vec![start.name.to_os_string()].into_iter(),
+            dir: start.dir,
+            removed_all: true,
+            depth: 0,
+            stat: start.stat,
+            newest: None,
+        }),
+        // the root directory is always a mount point
+        Ok(None) => plan.first = Some(PlannedAction::Skip { path: path.to_path_buf(), reason: SkipReason::MountPoint }),
+        Err(error) => plan.first = Some(PlannedAction::Error { path: path.to_path_buf(), error }),
+    }
+    plan
+}
+
+impl Plan<'_> {
+    /// decides what happens to the next entry of the innermost directory, returning a
+    /// directory to go into if that's what was decided
+    fn next_entry(frame: &mut Frame, name: OsString, options: &DeleteOptions) -> (PlannedAction, Option<Frame>) {
+        let path = frame.path.join(&name);
+        let stat = match frame.dir.stat_child(&name) {
+            Ok(stat) => stat,
+            // it's gone already, which doesn't keep the directory from going
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !options.strict_canonicalize => {
+                return (PlannedAction::Skip { path, reason: SkipReason::Vanished }, None);
+            }
+            Err(error) => {
+                frame.removed_all = false;
+                return (PlannedAction::Error { path, error }, None);
+            }
+        };
+        if frame.newest.as_ref() == Some(&name) {
+            frame.removed_all = false;
+            return (PlannedAction::Skip { path, reason: SkipReason::NewestInDir }, None);
+        }
+        let depth = frame.depth;
+        let entry = Entry { dir: &frame.dir, name: &name, path: &path, stat: &stat, parent_dev: frame.dev, depth };
+        let decision = match decide(&entry, options) {
+            Ok(decision) => decision,
+            Err(error) => {
+                frame.removed_all = false;
+                return (PlannedAction::Error { path, error }, None);
+            }
+        };
+        match decision {
+            Decision::Skip(SkipReason::Vanished) => (PlannedAction::Skip { path, reason: SkipReason::Vanished }, None),
+            Decision::Skip(reason) => {
+                frame.removed_all = false;
+                (PlannedAction::Skip { path, reason }, None)
+            }
+            Decision::RemoveSymlink | Decision::PruneSymlink | Decision::Delete(_) => (PlannedAction::Delete(path), None),
+            // the directory is still there once it's unmounted
+            Decision::Unmount => {
+                frame.removed_all = false;
+                (PlannedAction::Unmount(path), None)
+            }
+            Decision::Recurse(dir_stat) => match open_dir(&frame.dir, &name, &dir_stat, stat.is_symlink()) {
+                Ok(Some((dir, names))) => {
+                    let newest = options.preserve_newest.then(|| newest_file(&dir, &path, &names, dir_stat.dev(), depth + 1, options)).flatten();
+                    let child = Frame { dir, path: path.clone(), dev: dir_stat.dev(), names: names.into_iter(), removed_all: true, depth: depth + 1, stat: dir_stat, newest };
+                    (PlannedAction::EnterDir(path), Some(child))
+                }
+                Ok(None) => {
+                    frame.removed_all = false;
+                    (PlannedAction::Skip { path, reason: SkipReason::Changed }, None)
+                }
+                Err(error) => {
+                    frame.removed_all = false;
+                    (PlannedAction::Error { path, error }, None)
+                }
+            },
+        }
+    }
+}
+
+impl Iterator for Plan<'_> {
+    type Item = PlannedAction;
+
+    fn next(&mut self) -> Option<PlannedAction> {
+        if let Some(first) = self.first.take() {
+            return Some(first);
+        }
+        let frame = self.stack.last_mut()?;
+        match frame.names.next() {
+            Some(name) => {
+                let (action, child) = Self::next_entry(frame, name, self.options);
+                self.stack.extend(child);
+                Some(action)
+            }
+            // done with this directory, it goes if everything in it went
+            None => {
+                let done = self.stack.pop()?;
+                let parent = self.stack.last_mut()?;
+                // the directory itself is one above its entries
+                if done.depth <= self.options.min_depth {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::AboveMinDepth })
+                } else if done.depth == 1 && self.options.preserve_top {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::PreserveTop })
+                } else if self.options.age.as_ref().is_some_and(|age| !age.is_old_dir(&done.stat)) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::TooNew })
+                } else if done.depth == 1 && self.options.is_mounted(&done.path, &done.stat, parent.dev) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::MountPoint })
+                } else if let Some(reason) = self.options.check_ownership(&done.stat) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason })
+                } else if let Some(reason) = self.options.check_device(&done.path, &done.stat, parent.dev) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason })
+                } else if self.options.deselected(&done.path) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::Deselected })
+                } else if self.options.rename.is_some() {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::Renaming })
+                } else if done.removed_all && self.options.keeps_structure(&done.path) {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::KeptStructure })
+                } else if done.removed_all {
+                    Some(PlannedAction::RemoveDir(done.path))
+                } else {
+                    parent.removed_all = false;
+                    Some(PlannedAction::Skip { path: done.path, reason: SkipReason::NotEmpty })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::dirfd::{Dir, Stat};
+use crate::plan::{plan, PlannedAction};
+use crate::{DeleteOptions, RunState, SkipReason};
+
+/// what `--apply-plan` does with an entry of a plan file
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    Delete,
+    /// the directories a plan goes into, only checked so nothing is done in a swapped one
+    EnterDir,
+    RemoveDir,
+    Unmount,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Delete => "delete",
+            Action::EnterDir => "enter_dir",
+            Action::RemoveDir => "remove_dir",
+            Action::Unmount => "unmount",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Action> {
+        match name {
+            "delete" => Some(Action::Delete),
+            "enter_dir" => Some(Action::EnterDir),
+            "remove_dir" => Some(Action::RemoveDir),
+            "unmount" => Some(Action::Unmount),
+            _ => None,
+        }
+    }
+}
+
+/// an entry of a plan file, with what the path was when the plan was made
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Planned {
+    pub action: Action,
+    pub path: PathBuf,
+    pub dev: u64,
+    pub ino: u64,
+    /// the modification time in nanoseconds since the epoch
+    pub mtime: i128,
+}
+
+impl Planned {
+    /// returns true if `stat` is still the same thing the plan was made for. a directory's
+    /// mtime changes as its contents go, so only files are held to it
+    fn matches(&self, stat: &Stat) -> bool {
+        stat.dev() == self.dev && stat.ino() == self.ino && (self.action != Action::Delete || mtime(stat) == self.mtime)
+    }
+}
+
+fn mtime(stat: &Stat) -> i128 {
+    match stat.modified().duration_since(std::time::UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// writes what a run starting at `path` would do to `file` as json, one action per line so
+/// it reads well in a diff. returns how many actions there are for `--apply-plan` to do
+pub fn write(file: &Path, path: &Path, options: &DeleteOptions) -> io::Result<usize> {
+    let start = path.canonicalize()?;
+    let mut lines = Vec::new();
+    let mut count = 0;
+    for action in plan(&start, options) {
+        let (action, path) = match action {
+            PlannedAction::Delete(path) => (Action::Delete, path),
+            PlannedAction::EnterDir(path) => (Action::EnterDir, path),
+            PlannedAction::RemoveDir(path) => (Action::RemoveDir, path),
+            PlannedAction::Unmount(path) => (Action::Unmount, path),
+            // these aren't done, they're only there for whoever reads the plan
+            PlannedAction::Skip { path, reason } => {
+                if let Some(path) = path.to_str() {
+                    lines.push(format!("{{\"action\":\"skip\",\"path\":{},\"reason\":\"{}\"}}", json_string(path), reason.name()));
+                }
+                continue;
+            }
+            PlannedAction::Error { path, error } => {
+                if let Some(path) = path.to_str() {
+                    lines.push(format!("{{\"action\":\"error\",\"path\":{},\"error\":{}}}", json_string(path), json_string(&error.to_string())));
+                }
+                continue;
+            }
+        };
+        // json strings can't hold bytes that aren't utf-8
+        let Some(utf8) = path.to_str() else {
+            println!("{} isn't utf-8, it can't go in the plan so it will be kept", path.display());
+            continue;
+        };
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("error reading {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let mtime = metadata.mtime() as i128 * 1_000_000_000 + metadata.mtime_nsec() as i128;
+        lines.push(format!(
+            "{{\"action\":\"{}\",\"path\":{},\"dev\":{},\"ino\":{},\"mtime\":{}}}",
+            action.name(), json_string(utf8), metadata.dev(), metadata.ino(), mtime,
+        ));
+        if action != Action::EnterDir {
+            count += 1;
+        }
+    }
+    let mut out = io::BufWriter::new(std::fs::File::create(file)?);
+    writeln!(out, "{{\"start\":{},\"actions\":[", json_string(&start.to_string_lossy()))?;
+    for (i, line) in lines.iter().enumerate() {
+        let comma = if i + 1 < lines.len() { "," } else { "" };
+        writeln!(out, "  {}{}", line, comma)?;
+    }
+    writeln!(out, "]}}")?;
+    out.flush()?;
+    Ok(count)
+}
+
+/// a json value, only as much of json as plan files need
+enum Value {
+    String(String),
+    /// kept as written, since mtimes don't fit in an f64
+    Number(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_number<T: std::str::FromStr>(&self) -> Option<T> {
+        match self {
+            Value::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.at).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn eat(&mut self, b: u8) -> Option<()> {
+        self.skip_whitespace();
+        (self.bytes.get(self.at) == Some(&b)).then(|| self.at += 1)
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match *self.bytes.get(self.at)? {
+            b'"' => self.string().map(Value::String),
+            b'[' => {
+                self.at += 1;
+                let mut items = Vec::new();
+                if self.eat(b']').is_some() {
+                    return Some(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.eat(b']').is_some() {
+                        return Some(Value::Array(items));
+                    }
+                    self.eat(b',')?;
+                }
+            }
+            b'{' => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                if self.eat(b'}').is_some() {
+                    return Some(Value::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.eat(b':')?;
+                    fields.push((key, self.value()?));
+                    if self.eat(b'}').is_some() {
+                        return Some(Value::Object(fields));
+                    }
+                    self.eat(b',')?;
+                }
+            }
+            _ => {
+                let start = self.at;
+                while self.bytes.get(self.at).is_some_and(|b| b.is_ascii_alphanumeric() || b"-+.".contains(b)) {
+                    self.at += 1;
+                }
+                let word = std::str::from_utf8(&self.bytes[start..self.at]).ok()?;
+                match word {
+                    "" => None,
+                    "true" | "false" | "null" => Some(Value::Other),
+                    number => Some(Value::Number(number.to_string())),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.bytes.get(self.at) != Some(&b'"') {
+            return None;
+        }
+        self.at += 1;
+        let mut out = Vec::new();
+        loop {
+            match *self.bytes.get(self.at)? {
+                b'"' => break,
+                b'\\' => {
+                    self.at += 1;
+                    match *self.bytes.get(self.at)? {
+                        b'n' => out.push(b'\n'),
+                        b't' => out.push(b'\t'),
+                        b'r' => out.push(b'\r'),
+                        b'b' => out.push(8),
+                        b'f' => out.push(12),
+                        b'u' => {
+                            let hex = std::str::from_utf8(self.bytes.get(self.at + 1..self.at + 5)?).ok()?;
+                            let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            self.at += 4;
+                        }
+                        b => out.push(b),
+                    }
+                }
+                b => out.push(b),
+            }
+            self.at += 1;
+        }
+        self.at += 1;
+        String::from_utf8(out).ok()
+    }
+}
+
+/// reads a plan file written by `write`, after someone's had a look at it and maybe taken
+/// some lines out, returning where the plan started and what to do. anything that isn't for
+/// `--apply-plan` to do is left out
+pub fn read(file: &Path) -> io::Result<(PathBuf, Vec<Planned>)> {
+    let contents = std::fs::read(file)?;
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't a plan file: {}", file.display(), what));
+    let mut parser = Parser { bytes: &contents, at: 0 };
+    let plan = parser.value().ok_or_else(|| invalid("it isn't valid json"))?;
+    let Some(start) = plan.get("start").and_then(Value::as_str) else {
+        return Err(invalid("it doesn't say where it starts"));
+    };
+    let Some(Value::Array(actions)) = plan.get("actions") else {
+        return Err(invalid("it has no list of actions"));
+    };
+    let mut planned = Vec::new();
+    for action in actions {
+        let Some(kind) = action.get("action").and_then(Value::as_str) else {
+            return Err(invalid("an action doesn't say what it is"));
+        };
+        let Some(kind) = Action::parse(kind) else {
+            continue;
+        };
+        let entry = (|| Some(Planned {
+            action: kind,
+            path: PathBuf::from(action.get("path")?.as_str()?),
+            dev: action.get("dev")?.as_number()?,
+            ino: action.get("ino")?.as_number()?,
+            mtime: action.get("mtime")?.as_number()?,
+        }))();
+        planned.push(entry.ok_or_else(|| invalid("an action is missing its path, dev, ino or mtime"))?);
+    }
+    Ok((PathBuf::from(start), planned))
+}
+
+/// does what a plan file says, in order, as long as everything is still what it was when the
+/// plan was made. anything that changed is skipped and reported
+pub fn apply(planned: &[Planned], options: &DeleteOptions, state: &mut RunState) {
+    // the directories the plan went into, anything in one that's been swapped out is left alone
+    let dirs: HashMap<&Path, &Planned> = planned.iter()
+        .filter(|p| p.action == Action::EnterDir)
+        .map(|p| (p.path.as_path(), p))
+        .collect();
+    for entry in planned {
+        if state.stopped {
+            break;
+        }
+        let path = entry.path.as_path();
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            state.skip(path, SkipReason::MountPoint);
+            continue;
+        };
+        let opened = Dir::open(parent).and_then(|dir| dir.stat().map(|stat| (dir, stat)));
+        let (dir, parent_stat) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                println!("error reading {}: {}", parent.display(), e);
+                state.record_failed(parent, &e);
+                continue;
+            }
+        };
+        if dirs.get(parent).is_some_and(|p| !p.matches(&parent_stat)) {
+            state.skip(path, SkipReason::ChangedSincePlan);
+            continue;
+        }
+        let stat = match dir.stat_child(name) {
+            Ok(stat) => stat,
+            Err(e) => {
+                println!("error reading {}: {}", path.display(), e);
+                state.record_failed(path, &e);
+                continue;
+            }
+        };
+        if !entry.matches(&stat) {
+            state.skip(path, SkipReason::ChangedSincePlan);
+            continue;
+        }
+        match entry.action {
+            Action::EnterDir => {}
+            Action::Delete => {
+                if options.verbose { println!("deleting {}", path.display()); }
+                if options.dryrun {
+                    if options.verbose { println!("(dryrun) did nothing"); }
+                } else if let Err(e) = crate::remove_file(&dir, name, path, &stat, options) {
+                    println!("error deleting {}: {}", path.display(), e);
+                    state.record_failed(path, &e);
+                    continue;
+                }
+                state.record_deleted(path, &stat, options);
+            }
+            Action::RemoveDir => {
+                crate::wait_for_copies(options, state);
+                // a symlink that was gone into is removed as the link
+                let empty = stat.is_symlink() || dir.open_child(name, false).and_then(|d| d.entries()).is_ok_and(|e| e.is_empty());
+                if !empty && !options.dryrun {
+                    state.skip(path, SkipReason::NotEmpty);
+                    continue;
+                }
+                println!("removing directory {}", path.display());
+                if options.dryrun {
+                    println!("(dryrun) did nothing");
+                } else if let Err(e) = crate::clearing_attrs(&dir, name, path, options, || if stat.is_symlink() { dir.unlink(name) } else { dir.remove_dir(name) }) {
+                    println!("error deleting {}: {}", path.display(), e);
+                    state.record_failed(path, &e);
+                    continue;
+                }
+                state.record_dir_removed(path, &stat, options);
+            }
+            Action::Unmount => {
+                println!("{} is a mount point, unmounting", path.display());
+                if options.dryrun {
+                    println!("(dryrun) did nothing");
+                } else if let Err(e) = crate::unmount(path, options.unmount_failure.is_none()) {
+                    println!("error unmounting {}: {}", path.display(), e);
+                    state.record_failed(path, &e);
+                }
+            }
+        }
+    }
+    crate::wait_for_copies(options, state);
+    if let Some(changed) = state.skipped.get(SkipReason::ChangedSincePlan.name()) {
+        println!("{} changed since the plan was made and were left alone", crate::plural(*changed, "thing"));
+    }
+}
@@ -0,0 +1,22 @@
+use std::ffi::OsString;
+use std::io;
+use crate::dirfd::{Dir, Stat};
+
+/// how many stats are in flight at once with `--parallel-stat`
+pub const JOBS: usize = 8;
+
+/// stats every name in `dir` with `jobs` threads, returning the results in the same order as
+/// `names`. each stat is a round trip to the server on a network filesystem, so having a few
+/// going at once hides most of the waiting
+pub fn stat_all(dir: &Dir, names: &[OsString], jobs: usize) -> Vec<io::Result<Stat>> {
+    if names.len() < 2 || jobs < 2 {
+        return names.iter().map(|name| dir.stat_child(name)).collect();
+    }
+    let chunk = names.len().div_ceil(jobs);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = names.chunks(chunk)
+            .map(|names| scope.spawn(move || names.iter().map(|name| dir.stat_child(name)).collect::<Vec<_>>()))
+            .collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
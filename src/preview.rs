@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use crate::plan::{plan, PlannedAction};
+use crate::{format_size, DeleteOptions, SkipReason};
+
+/// how many of the biggest files the preview lists
+const LARGEST_SHOWN: usize = 5;
+
+/// what a run would do, added up, for `--prompt-summary-before` and `--preview`
+#[derive(Default)]
+pub struct Preview {
+    pub files: usize,
+    pub symlinks: usize,
+    pub dirs: usize,
+    /// mount points that would be skipped or unmounted rather than gone into
+    pub mount_points: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub bytes: u64,
+    /// the biggest files that would go, biggest first
+    pub largest: Vec<(PathBuf, u64)>,
+}
+
+impl Preview {
+    /// goes through what a run would do without doing any of it
+    pub fn gather(path: &Path, options: &DeleteOptions) -> Preview {
+        let mut preview = Preview::default();
+        for action in plan(path, options) {
+            preview.add(action);
+        }
+        preview
+    }
+
+    /// `gather`, listing the first `shown` paths that would go along the way, for `--preview`
+    pub fn sample(path: &Path, options: &DeleteOptions, shown: usize) -> Preview {
+        let mut preview = Preview::default();
+        let mut listed = 0;
+        for action in plan(path, options) {
+            if let PlannedAction::Delete(path) | PlannedAction::RemoveDir(path) = &action {
+                if listed < shown {
+                    println!("would delete {}", path.display());
+                }
+                listed += 1;
+            }
+            preview.add(action);
+        }
+        if listed > shown {
+            println!("... and {} more", format_count(listed - shown));
+        }
+        preview
+    }
+
+    fn add(&mut self, action: PlannedAction) {
+        match action {
+            PlannedAction::Delete(path) => {
+                let metadata = match path.symlink_metadata() {
+                    Ok(m) => m,
+                    Err(_) => {
+                        self.errors += 1;
+                        return;
+                    }
+                };
+                if metadata.is_symlink() {
+                    self.symlinks += 1;
+                    return;
+                }
+                self.files += 1;
+                self.bytes += metadata.len();
+                self.largest.push((path, metadata.len()));
+                self.largest.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+                self.largest.truncate(LARGEST_SHOWN);
+            }
+            PlannedAction::RemoveDir(_) => self.dirs += 1,
+            PlannedAction::Unmount(_)
+            | PlannedAction::Skip { reason: SkipReason::MountPoint | SkipReason::OtherFilesystem, .. } => {
+                self.mount_points += 1
+            }
+            PlannedAction::Skip { .. } => self.skipped += 1,
+            PlannedAction::Error { .. } => self.errors += 1,
+            PlannedAction::EnterDir(_) => {}
+        }
+    }
+
+    pub fn print(&self) {
+        println!("this will delete:");
+        println!("  {} regular files, {} in total", self.files, format_size(self.bytes));
+        println!("  {} symlinks", self.symlinks);
+        println!("  {} directories", self.dirs);
+        println!("and leave alone:");
+        println!("  {} mount points", self.mount_points);
+        println!("  {} other skipped entries", self.skipped);
+        if self.errors > 0 {
+            println!("  {} entries that couldn't be looked at", self.errors);
+        }
+        if !self.largest.is_empty() {
+            println!("the largest files:");
+            for (path, size) in &self.largest {
+                println!("  {} {}", format_size(*size), path.display());
+            }
+        }
+    }
+}
+
+/// a count with commas between the thousands, like 12,340
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
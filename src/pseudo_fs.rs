@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+/// filesystems the kernel makes up as they're read, where deleting anything is at best
+/// meaningless and at worst pokes at the kernel or the hardware
+const TYPES: &[&str] = &["proc", "sysfs", "devtmpfs", "cgroup", "cgroup2", "debugfs", "tracefs", "securityfs", "configfs", "efivarfs", "bpf"];
+
+/// the devices of every pseudo filesystem that's mounted, with their types, going by
+/// /proc/self/mountinfo. nothing is found if that can't be read
+pub fn mounted() -> HashMap<u64, &'static str> {
+    let mut found = HashMap::new();
+    let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+        return found;
+    };
+    for line in mountinfo.lines() {
+        // the optional fields in the middle end at a lone -, and the type is right after it
+        let Some((before, after)) = line.split_once(" - ") else { continue };
+        let Some(kind) = after.split(' ').next().and_then(|kind| TYPES.iter().find(|&&t| t == kind)) else { continue };
+        let Some((major, minor)) = before.split(' ').nth(2).and_then(|dev| dev.split_once(':')) else { continue };
+        if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+            found.insert(libc::makedev(major, minor), *kind);
+        }
+    }
+    found
+}
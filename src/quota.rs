@@ -0,0 +1,74 @@
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::Path;
+use crate::dirfd::{Dir, Stat};
+
+/// `PRJQUOTA` from linux/quota.h, libc doesn't have it
+#[cfg(target_os = "linux")]
+const PRJQUOTA: libc::c_int = 2;
+/// `FS_IOC_FSGETXATTR` from linux/fs.h, `_IOR('X', 31, struct fsxattr)`
+#[cfg(target_os = "linux")]
+const FS_IOC_FSGETXATTR: libc::c_ulong = 0x801c581f;
+
+/// `struct fsxattr` from linux/fs.h, we only want the project out of it
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct FsXattr {
+    xflags: u32,
+    extsize: u32,
+    nextents: u32,
+    projid: u32,
+    cowextsize: u32,
+    pad: [u8; 8],
+}
+
+/// the project quota id of an open file. files on a filesystem without project quotas are
+/// all in project 0
+pub fn project(fd: RawFd) -> io::Result<u32> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut attr = FsXattr::default();
+        // the ioctl number's type differs between libcs, musl wants it as a negative int
+        if unsafe { libc::ioctl(fd, FS_IOC_FSGETXATTR as _, &mut attr) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(attr.projid)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = fd;
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// the project of the entry `name` in `dir`, if it's a regular file. opening anything else
+/// might do something, like a device would
+pub fn project_of(dir: &Dir, name: &OsStr, stat: &Stat) -> Option<u32> {
+    if !stat.is_file() {
+        return None;
+    }
+    let file = dir.open_child_file(name).ok()?;
+    project(file.as_raw_fd()).ok()
+}
+
+/// how many bytes the files in `project` take up on the filesystem holding `path`, as the
+/// quota sees it. this needs root, and linux 5.14 or newer for `quotactl_fd`
+pub fn usage(path: &Path, project: u32) -> io::Result<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let file = std::fs::File::open(path)?;
+        let mut quota = std::mem::MaybeUninit::<libc::dqblk>::zeroed();
+        let cmd = libc::QCMD(libc::Q_GETQUOTA, PRJQUOTA);
+        if unsafe { libc::syscall(libc::SYS_quotactl_fd, file.as_raw_fd(), cmd, project, quota.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { quota.assume_init() }.dqb_curspace)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (path, project);
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
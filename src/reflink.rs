@@ -0,0 +1,69 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// whether copies into the trash or a backup share their blocks with the original, on
+/// filesystems that can (btrfs, XFS). this is `--reflink`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Reflink {
+    /// always make a real copy
+    #[default]
+    Never,
+    /// try a reflink and make a real copy if the filesystem can't
+    Auto,
+    /// fail instead of making a real copy
+    Always,
+}
+
+impl Reflink {
+    pub fn parse(reflink: &str) -> Option<Reflink> {
+        match reflink {
+            "never" => Some(Reflink::Never),
+            "auto" => Some(Reflink::Auto),
+            "always" => Some(Reflink::Always),
+            _ => None,
+        }
+    }
+}
+
+/// makes `to` a clone of `from` that shares its blocks
+#[cfg(target_os = "linux")]
+fn clone_file(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::unix::prelude::*;
+    let source = File::open(from)?;
+    let permissions = source.metadata()?.permissions();
+    let destination = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(to)?;
+    if unsafe { libc::ioctl(destination.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } != 0 {
+        let e = io::Error::last_os_error();
+        drop(destination);
+        let _ = std::fs::remove_file(to);
+        return Err(e);
+    }
+    destination.set_permissions(permissions)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clone_file(_from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// returns true if a reflink failed because the filesystems can't do one here, rather than
+/// because something is wrong with the files
+fn unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Unsupported
+        || matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL | libc::ENOTTY))
+}
+
+/// copies a regular file, as a reflink if `reflink` says to
+pub(crate) fn copy(from: &Path, to: &Path, reflink: Reflink) -> io::Result<()> {
+    if reflink == Reflink::Never {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    match clone_file(from, to) {
+        Err(e) if reflink == Reflink::Auto && unsupported(&e) => {
+            log::debug!("couldn't reflink {} to {}, copying instead: {}", from.display(), to.display(), e);
+            std::fs::copy(from, to).map(|_| ())
+        }
+        other => other,
+    }
+}
@@ -0,0 +1,73 @@
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::dirfd::Dir;
+use crate::move_to::Collision;
+
+/// how many `.N` suffixes to try before giving up on a name
+const MAX_SUFFIX: usize = 10000;
+
+/// renames files where they are instead of deleting them, for `--rename`
+pub struct Rename {
+    /// what goes before and after the original name
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+    pub collision: Collision,
+}
+
+impl Rename {
+    /// parses a template like `{name}.deleted` or `DELETED_{name}`, which has to have the
+    /// original name in it exactly once and can't make it a path
+    pub fn parse(template: &str, collision: Collision) -> Option<Rename> {
+        let (prefix, suffix) = template.split_once("{name}")?;
+        if suffix.contains("{name}") || template.contains('/') || (prefix.is_empty() && suffix.is_empty()) {
+            return None;
+        }
+        Some(Rename { prefix: prefix.as_bytes().to_vec(), suffix: suffix.as_bytes().to_vec(), collision })
+    }
+
+    fn new_name(&self, name: &OsStr) -> OsString {
+        OsString::from_vec([&self.prefix, name.as_bytes(), &self.suffix].concat())
+    }
+
+    /// returns true if `name` looks like one we'd have given a file, with or without the `.N`
+    /// of a collision, so an earlier run's renames aren't renamed again
+    pub fn is_renamed(&self, name: &OsStr) -> bool {
+        let matches = |name: &[u8]| {
+            name.len() > self.prefix.len() + self.suffix.len() && name.starts_with(&self.prefix) && name.ends_with(&self.suffix)
+        };
+        let name = name.as_bytes();
+        let without_n = match name.iter().rposition(|&b| b == b'.') {
+            Some(dot) if dot + 1 < name.len() && name[dot + 1..].iter().all(u8::is_ascii_digit) => &name[..dot],
+            _ => name,
+        };
+        matches(name) || matches(without_n)
+    }
+
+    /// returns true if the file would be skipped because its new name is taken
+    pub fn collides(&self, dir: &Dir, name: &OsStr) -> bool {
+        self.collision == Collision::Skip && dir.stat_child(&self.new_name(name)).is_ok()
+    }
+
+    /// renames the file `name` in `dir` (which lives at `path`), never over something else.
+    /// returns where it is now
+    pub fn rename(&self, dir: &Dir, name: &OsStr, path: &Path) -> io::Result<PathBuf> {
+        let new_name = self.new_name(name);
+        let mut candidate = new_name.clone();
+        for n in 1..=MAX_SUFFIX {
+            match dir.rename_noreplace(name, &candidate) {
+                Ok(()) => {
+                    log::debug!("renamed {} to {}", path.display(), candidate.to_string_lossy());
+                    return Ok(path.with_file_name(candidate));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && self.collision == Collision::Suffix => {
+                    candidate = new_name.clone();
+                    candidate.push(format!(".{}", n));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("every name up to {}.{} is taken", new_name.to_string_lossy(), MAX_SUFFIX)))
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use crate::plan::{plan, PlannedAction};
+use crate::{format_size, parse_duration, parse_size, DeleteOptions};
+
+const HELP: &str = "\
+  exclude <glob>        keep what matches, and everything in a directory that matches
+  include <glob>        only delete files that match
+  older-than <duration> only delete files last modified longer ago than this, like 30d
+  larger-than <size>    only delete files bigger than this, like 10M
+  filters               list the filters so far
+  drop <n>              take away the nth filter
+  reset                 take away every filter
+  count                 say how much is left in the plan
+  show [n]              list what's left in the plan, the first 50 unless told how many
+  commit                delete what's left in the plan
+  quit                  stop without deleting anything";
+
+/// something the plan would remove, with what the filters go by
+struct Entry {
+    path: PathBuf,
+    is_dir: bool,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// one of the things typed in to narrow the plan down
+enum Filter {
+    Exclude(String, Gitignore),
+    Include(String, Gitignore),
+    OlderThan(String, SystemTime),
+    LargerThan(String, u64),
+}
+
+impl Filter {
+    fn parse(start: &Path, command: &str, argument: &str, ignore_case: bool) -> Result<Filter, String> {
+        let glob = || {
+            let mut builder = GitignoreBuilder::new(start);
+            builder.case_insensitive(ignore_case).map_err(|e| e.to_string())?;
+            builder.add_line(None, argument).map_err(|e| e.to_string())?;
+            builder.build().map_err(|e| e.to_string())
+        };
+        match command {
+            "exclude" => Ok(Filter::Exclude(argument.to_string(), glob()?)),
+            "include" => Ok(Filter::Include(argument.to_string(), glob()?)),
+            "older-than" => match parse_duration(argument) {
+                Some(age) => Ok(Filter::OlderThan(argument.to_string(), SystemTime::now().checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH))),
+                None => Err("that isn't a duration, like 30s, 10m or 2h".to_string()),
+            },
+            "larger-than" => match parse_size(argument) {
+                Some(size) => Ok(Filter::LargerThan(argument.to_string(), size)),
+                None => Err("that isn't a size".to_string()),
+            },
+            _ => Err(format!("there's no {} command, try help", command)),
+        }
+    }
+
+    /// returns true if the filter takes `entry` out of the plan. only `exclude` says anything
+    /// about directories, the rest go by whether everything in them is going
+    fn excludes(&self, entry: &Entry) -> bool {
+        match self {
+            Filter::Exclude(_, glob) => glob.matched_path_or_any_parents(&entry.path, entry.is_dir).is_ignore(),
+            _ if entry.is_dir => false,
+            Filter::Include(_, glob) => !glob.matched(&entry.path, false).is_ignore(),
+            Filter::OlderThan(_, cutoff) => entry.modified >= *cutoff,
+            Filter::LargerThan(_, size) => entry.size <= *size,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Filter::Exclude(glob, _) => format!("exclude {}", glob),
+            Filter::Include(glob, _) => format!("include {}", glob),
+            Filter::OlderThan(age, _) => format!("older-than {}", age),
+            Filter::LargerThan(size, _) => format!("larger-than {}", size),
+        }
+    }
+}
+
+/// what's left of the plan once the filters are applied, a directory only goes if everything
+/// in it does
+fn evaluate<'a>(path: &Path, entries: &'a [Entry], filters: &[Filter]) -> Vec<&'a Entry> {
+    let mut blocked = HashSet::new();
+    for entry in entries {
+        if filters.iter().any(|f| f.excludes(entry)) {
+            blocked.insert(entry.path.as_path());
+            for ancestor in entry.path.ancestors().skip(1) {
+                if !blocked.insert(ancestor) || ancestor == path {
+                    break;
+                }
+            }
+        }
+    }
+    entries.iter().filter(|e| !blocked.contains(e.path.as_path())).collect()
+}
+
+fn count(kept: &[&Entry]) -> String {
+    let files = kept.iter().filter(|e| !e.is_dir).count();
+    let size: u64 = kept.iter().map(|e| e.size).sum();
+    format!("{} files and {} directories, {} in total", files, kept.len() - files, format_size(size))
+}
+
+/// plans a run once, then lets the user narrow it down with filters at a prompt, going over
+/// what was planned again each time rather than the disk. returns what's left when they
+/// commit, or `None` if they quit
+pub fn refine_plan(path: &Path, options: &DeleteOptions) -> io::Result<Option<HashSet<PathBuf>>> {
+    let mut entries = Vec::new();
+    for action in plan(path, options) {
+        let (path, is_dir) = match action {
+            PlannedAction::Delete(path) => (path, false),
+            PlannedAction::RemoveDir(path) => (path, true),
+            _ => continue,
+        };
+        let Ok(metadata) = path.symlink_metadata() else { continue };
+        let size = if metadata.is_file() { metadata.len() } else { 0 };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push(Entry { path, is_dir, size, modified });
+    }
+    println!("the plan is {}, type help for what you can do with it", count(&entries.iter().collect::<Vec<_>>()));
+
+    let mut filters: Vec<Filter> = Vec::new();
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("saferm> ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            println!();
+            return Ok(None);
+        };
+        let line = line.trim();
+        let (command, argument) = line.split_once(char::is_whitespace).map_or((line, ""), |(c, a)| (c, a.trim()));
+        match command {
+            "" => {}
+            "help" => println!("{}", HELP),
+            "quit" | "exit" => return Ok(None),
+            "count" => println!("{}", count(&evaluate(path, &entries, &filters))),
+            "show" => {
+                let kept = evaluate(path, &entries, &filters);
+                let shown = if argument.is_empty() { Some(50) } else { argument.parse().ok() };
+                let Some(shown) = shown else {
+                    println!("show takes how many to list");
+                    continue;
+                };
+                for entry in kept.iter().take(shown) {
+                    let slash = if entry.is_dir { "/" } else { "" };
+                    println!("  {:>10} {}{}", format_size(entry.size), entry.path.display(), slash);
+                }
+                if kept.len() > shown {
+                    println!("  and {} more", kept.len() - shown);
+                }
+            }
+            "filters" if filters.is_empty() => println!("no filters yet"),
+            "filters" => {
+                for (i, filter) in filters.iter().enumerate() {
+                    println!("  {}: {}", i + 1, filter.describe());
+                }
+            }
+            "drop" => match argument.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= filters.len() => {
+                    let filter = filters.remove(n - 1);
+                    println!("dropped {}, the plan is now {}", filter.describe(), count(&evaluate(path, &entries, &filters)));
+                }
+                _ => println!("drop takes the number of a filter, filters lists them"),
+            },
+            "reset" => {
+                filters.clear();
+                println!("the plan is back to {}", count(&entries.iter().collect::<Vec<_>>()));
+            }
+            "commit" => {
+                let kept = evaluate(path, &entries, &filters);
+                return Ok(Some(kept.into_iter().map(|e| e.path.clone()).collect()));
+            }
+            _ if argument.is_empty() => println!("there's no {} command on its own, try help", command),
+            _ => match Filter::parse(path, command, argument, options.filters.ignore_case) {
+                Ok(filter) => {
+                    filters.push(filter);
+                    println!("the plan is now {}", count(&evaluate(path, &entries, &filters)));
+                }
+                Err(e) => println!("{}", e),
+            },
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::plan::{plan, PlannedAction};
+use crate::DeleteOptions;
+
+const HEADER: &str = "\
+# everything below would be deleted. remove the lines of anything you want to keep,
+# then save and quit. quit with an error (:cq in vim) to cancel the whole thing
+";
+
+/// lists what a run would delete in the user's editor and returns what's still listed once
+/// they're done with it, or `None` if the editor exited with an error
+pub fn edit_plan(path: &Path, options: &DeleteOptions) -> io::Result<Option<HashSet<PathBuf>>> {
+    let mut listed = HashSet::new();
+    let mut contents = HEADER.as_bytes().to_vec();
+    for action in plan(path, options) {
+        let path = match action {
+            PlannedAction::Delete(path) | PlannedAction::RemoveDir(path) => path,
+            _ => continue,
+        };
+        // one path per line can't hold these, so they go in case
+        if path.as_os_str().as_bytes().contains(&b'\n') {
+            println!("{} has a newline in its name, it can't be listed so it will be kept", path.display());
+            continue;
+        }
+        contents.extend_from_slice(path.as_os_str().as_bytes());
+        contents.push(b'\n');
+        listed.insert(path);
+    }
+
+    let file_path = std::env::temp_dir().join(format!("saferm-review-{}.txt", std::process::id()));
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&file_path)?;
+    file.write_all(&contents)?;
+    drop(file);
+    let result = run_editor(&file_path).and_then(|ok| if ok { std::fs::read(&file_path).map(Some) } else { Ok(None) });
+    let _ = std::fs::remove_file(&file_path);
+    let Some(edited) = result? else {
+        return Ok(None);
+    };
+
+    let mut kept = HashSet::new();
+    for line in edited.split(|b| *b == b'\n') {
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(line));
+        // only ever narrow down what was planned, never add to it
+        if listed.contains(&path) {
+            kept.insert(path);
+        } else {
+            println!("{} wasn't in the list, ignoring it", path.display());
+        }
+    }
+    Ok(Some(kept))
+}
+
+/// opens `path` in `$VISUAL` or `$EDITOR`, through the shell so they can have arguments
+fn run_editor(path: &Path) -> io::Result<bool> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    Ok(status.success())
+}
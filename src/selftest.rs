@@ -0,0 +1,141 @@
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use crate::{delete, DeleteOptions, RunState};
+
+/// one of the checks, it gets a tree of its own to make and delete
+struct Case {
+    name: &'static str,
+    /// fills in the starting directory, the directory it's in can be used for things that
+    /// have to be outside of it
+    build: fn(&Path, &Path) -> io::Result<()>,
+    /// what's different from the options with every check on
+    options: fn(&Path, &mut DeleteOptions),
+    /// says what went wrong, if anything
+    check: fn(&Path, &Path, &RunState) -> Result<(), String>,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "a symlink leading above the start isn't followed",
+        build: |case, start| {
+            std::fs::create_dir(case.join("outside"))?;
+            std::fs::write(case.join("outside").join("keep"), "")?;
+            std::os::unix::fs::symlink("../outside", start.join("up"))
+        },
+        options: |_, options| options.enter_symlinks = true,
+        check: |case, _, state| {
+            expect(case.join("outside").join("keep").exists(), "the file the symlink led to was deleted")?;
+            expect_skipped(state, "above_start")
+        },
+    },
+    Case {
+        name: "a mount point is left alone",
+        build: |_, start| {
+            std::fs::create_dir(start.join("mnt"))?;
+            std::fs::write(start.join("mnt").join("keep"), "")
+        },
+        // there's nothing to mount without root, so it's made out to be one the way bind
+        // mounts are found
+        options: |start, options| options.bind_mounts = Some([start.join("mnt")].into_iter().collect()),
+        check: |_, start, state| {
+            expect(start.join("mnt").join("keep").exists(), "the file in the mount point was deleted")?;
+            expect_skipped(state, "mount_point")
+        },
+    },
+    Case {
+        name: "hidden files are kept",
+        build: |_, start| std::fs::write(start.join(".hidden"), ""),
+        options: |_, _| {},
+        check: |_, start, state| {
+            expect(start.join(".hidden").exists(), "the hidden file was deleted")?;
+            expect_skipped(state, "hidden")
+        },
+    },
+    Case {
+        name: "read-only files are deleted",
+        build: |_, start| {
+            let file = start.join("read-only");
+            std::fs::write(&file, "")?;
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o444))
+        },
+        options: |_, _| {},
+        check: |_, start, _| expect(!start.exists(), "the read-only file was left behind"),
+    },
+    Case {
+        name: "deep nesting is deleted all the way down",
+        build: |_, start| {
+            let mut dir = start.to_path_buf();
+            for _ in 0..64 {
+                dir.push("d");
+            }
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join("file"), "")
+        },
+        options: |_, _| {},
+        check: |_, start, _| expect(!start.exists(), "part of the nested tree was left behind"),
+    },
+];
+
+fn expect(ok: bool, what: &str) -> Result<(), String> {
+    if ok { Ok(()) } else { Err(what.into()) }
+}
+
+fn expect_skipped(state: &RunState, reason: &str) -> Result<(), String> {
+    expect(state.skipped.contains_key(reason), &format!("nothing was skipped as {}", reason))
+}
+
+/// makes a tree for every check in a new directory under `parent`, deletes each with the
+/// options that are supposed to keep it safe and reports which held. nothing outside the new
+/// directory is touched, and it's cleaned up afterwards. returns how many checks failed
+pub fn selftest(parent: &Path) -> io::Result<usize> {
+    let root = parent.canonicalize()?.join(format!("saferm-selftest-{}", std::process::id()));
+    std::fs::create_dir(&root)?;
+    println!("running the self test in {}", root.display());
+    let mut results = Vec::new();
+    for (i, case) in CASES.iter().enumerate() {
+        let result = run(case, &root.join(format!("case{}", i)));
+        results.push((case.name, result));
+    }
+    cleanup(&root)?;
+
+    println!();
+    let mut failed = 0;
+    for (name, result) in &results {
+        match result {
+            Ok(()) => println!("pass: {}", name),
+            Err(e) => {
+                println!("FAIL: {}: {}", name, e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} of {} checks passed", results.len() - failed, results.len());
+    Ok(failed)
+}
+
+fn run(case: &Case, dir: &Path) -> Result<(), String> {
+    let start = dir.join("start");
+    std::fs::create_dir_all(&start).map_err(|e| format!("couldn't make the tree: {}", e))?;
+    // this is next to the start, so nothing should ever get to it
+    std::fs::write(dir.join("beside"), "").map_err(|e| format!("couldn't make the tree: {}", e))?;
+    (case.build)(dir, &start).map_err(|e| format!("couldn't make the tree: {}", e))?;
+
+    let mut options = DeleteOptions::new(start.clone());
+    options.recursive = true;
+    (case.options)(&start, &mut options);
+    let mut state = RunState::default();
+    delete(&start.to_string_lossy(), &options, &mut state);
+
+    expect(dir.join("beside").exists(), "a file next to the starting directory was deleted")?;
+    (case.check)(dir, &start, &state)
+}
+
+/// removes what's left of the tree, without following anything out of it
+fn cleanup(root: &PathBuf) -> io::Result<()> {
+    match std::fs::symlink_metadata(root) {
+        Ok(m) if m.is_dir() => std::fs::remove_dir_all(root),
+        Ok(_) => std::fs::remove_file(root),
+        Err(_) => Ok(()),
+    }
+}
@@ -0,0 +1,33 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::Path;
+
+#[link(name = "selinux")]
+extern "C" {
+    fn lgetfilecon(path: *const libc::c_char, con: *mut *mut libc::c_char) -> libc::c_int;
+    fn lsetfilecon(path: *const libc::c_char, con: *const libc::c_char) -> libc::c_int;
+    fn freecon(con: *mut libc::c_char);
+}
+
+/// gives `to` the same selinux context as `from`, without following symlinks. files that
+/// don't have a context, or filesystems that don't do them, are left alone
+pub fn copy_context(from: &Path, to: &Path) -> io::Result<()> {
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+    let mut context = std::ptr::null_mut();
+    if unsafe { lgetfilecon(from.as_ptr(), &mut context) } < 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENODATA) | Some(libc::ENOTSUP) => Ok(()),
+            _ => Err(e),
+        };
+    }
+    let result = if unsafe { lsetfilecon(to.as_ptr(), context) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    };
+    unsafe { freecon(context) };
+    result
+}
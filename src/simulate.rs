@@ -0,0 +1,36 @@
+use std::cell::Cell;
+use std::io;
+
+/// fails a fraction of removals on purpose, for `--simulate-error`. this only exists in debug
+/// builds or with the `simulate-errors` feature, so a release build can never be told to fail
+pub struct SimulatedErrors {
+    fraction: f64,
+    /// xorshift state, there's no need for anything better than that here
+    state: Cell<u64>,
+}
+
+impl SimulatedErrors {
+    pub fn new(fraction: f64) -> SimulatedErrors {
+        let time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        let seed = time.as_nanos() as u64 ^ (std::process::id() as u64) << 32;
+        // xorshift gets stuck on 0
+        SimulatedErrors { fraction, state: Cell::new(seed | 1) }
+    }
+
+    fn next(&self) -> f64 {
+        let mut x = self.state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// returns an error for the fraction of removals that are meant to fail
+    pub fn maybe_fail(&self) -> io::Result<()> {
+        if self.next() < self.fraction {
+            return Err(io::Error::other("simulated error"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::dirfd::{Dir, Stat};
+use crate::{format_size, quota, DeleteOptions, RunState};
+
+/// how often to ask the filesystem how much space is free while deleting
+const RECHECK_EVERY: usize = 64;
+
+/// which files go first when we only delete until there's enough free space
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    Oldest,
+    Newest,
+    Largest,
+    /// least recently used first, by atime
+    Lru,
+}
+
+impl Order {
+    pub fn parse(order: &str) -> Option<Order> {
+        match order {
+            "oldest" => Some(Order::Oldest),
+            "newest" => Some(Order::Newest),
+            "largest" => Some(Order::Largest),
+            "lru" => Some(Order::Lru),
+            _ => None,
+        }
+    }
+}
+
+/// what there has to be enough of free before we stop deleting
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Goal {
+    /// bytes, for `--until-free`
+    Bytes(u64),
+    /// inodes, for `--until-free-inodes`
+    Inodes(u64),
+    /// the space a quota project takes up, for `--until-project-below`. this one's a ceiling
+    /// to get under rather than an amount to get free
+    Project { id: u32, below: u64 },
+}
+
+impl Goal {
+    fn target(self) -> u64 {
+        match self {
+            Goal::Bytes(n) | Goal::Inodes(n) => n,
+            Goal::Project { below, .. } => below,
+        }
+    }
+
+    /// how much of it is free on the filesystem holding `path`, or how much the project uses
+    fn measure(self, path: &Path) -> io::Result<u64> {
+        match self {
+            Goal::Bytes(_) => free_space(path),
+            Goal::Inodes(_) => free_inodes(path),
+            Goal::Project { id, .. } => quota::usage(path, id),
+        }
+    }
+
+    fn reached(self, n: u64) -> bool {
+        match self {
+            Goal::Bytes(target) | Goal::Inodes(target) => n >= target,
+            Goal::Project { below, .. } => n < below,
+        }
+    }
+
+    /// what the measurement should be after deleting something that gave back `gained`
+    fn after(self, n: u64, gained: u64) -> u64 {
+        match self {
+            Goal::Bytes(_) | Goal::Inodes(_) => n + gained,
+            Goal::Project { .. } => n.saturating_sub(gained),
+        }
+    }
+
+    /// how much deleting a file should free up, a hardlinked file doesn't give its inode back
+    /// but the next real check sorts that out
+    fn gained(self, stat: &Stat, options: &DeleteOptions) -> u64 {
+        match self {
+            Goal::Bytes(_) | Goal::Project { .. } => options.freed_size(stat),
+            Goal::Inodes(_) => 1,
+        }
+    }
+
+    fn format(self, n: u64) -> String {
+        match self {
+            Goal::Bytes(_) | Goal::Project { .. } => format_size(n),
+            Goal::Inodes(_) if n == 1 => "1 inode".to_string(),
+            Goal::Inodes(_) => format!("{} inodes", n),
+        }
+    }
+}
+
+/// a file that passed every check, held back so it can be deleted in order later
+pub struct Candidate {
+    pub path: PathBuf,
+    pub stat: Stat,
+    /// the quota project it's in, with `--quota-aware`
+    pub project: Option<u32>,
+}
+
+fn statvfs(path: &Path) -> io::Result<libc::statvfs> {
+    let path_cstr = CString::new(path.as_os_str().as_bytes())?;
+    let mut buf = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path_cstr.as_ptr(), buf.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { buf.assume_init() })
+}
+
+/// returns how many bytes are available to us on the filesystem holding `path`
+// the statvfs field types differ between platforms
+#[allow(clippy::unnecessary_cast)]
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    let buf = statvfs(path)?;
+    Ok(buf.f_bavail as u64 * buf.f_frsize as u64)
+}
+
+/// returns how many more files we could make on the filesystem holding `path`
+#[allow(clippy::unnecessary_cast)]
+pub fn free_inodes(path: &Path) -> io::Result<u64> {
+    Ok(statvfs(path)?.f_favail as u64)
+}
+
+/// deletes a held back candidate, making sure it's still the file we looked at
+pub(crate) fn delete_candidate(candidate: &Candidate, options: &DeleteOptions) -> io::Result<()> {
+    let parent = candidate.path.parent().unwrap_or(Path::new("/"));
+    let name = candidate.path.file_name().unwrap_or_default();
+    let dir = Dir::open(parent)?;
+    let stat = dir.stat_child(name)?;
+    if stat.dev() != candidate.stat.dev() || stat.ino() != candidate.stat.ino() {
+        return Err(io::Error::other("file was replaced since we looked at it"));
+    }
+    crate::remove_file(&dir, name, &candidate.path, &stat, options)
+}
+
+/// deletes the held back candidates in order until the filesystem has enough of `goal` free,
+/// or the project is under its limit
+pub fn delete_until_free(goal: Goal, order: Order, options: &DeleteOptions, state: &mut RunState) {
+    let mut candidates = std::mem::take(&mut state.candidates);
+    // deleting files in other projects wouldn't get this one any closer
+    if let Goal::Project { id, .. } = goal {
+        let before = candidates.len();
+        candidates.retain(|c| c.project == Some(id));
+        match before - candidates.len() {
+            0 => {}
+            1 => println!("leaving 1 file that isn't in project {}", id),
+            n => println!("leaving {} files that aren't in project {}", n, id),
+        }
+    }
+    match order {
+        Order::Oldest => candidates.sort_by_key(|c| c.stat.modified()),
+        Order::Newest => candidates.sort_by_key(|c| std::cmp::Reverse(c.stat.modified())),
+        Order::Largest => candidates.sort_by_key(|c| std::cmp::Reverse(c.stat.size())),
+        Order::Lru => candidates.sort_by_key(|c| c.stat.accessed()),
+    }
+
+    let target = goal.target();
+    let mut now = match goal.measure(&options.starting_dir) {
+        Ok(now) => now,
+        Err(e) => {
+            match goal {
+                Goal::Project { id, .. } => {
+                    println!("error checking the quota of project {}: {}", id, e);
+                    if e.raw_os_error() == Some(libc::ESRCH) {
+                        println!("  project quotas aren't turned on for this filesystem");
+                    }
+                }
+                _ => println!("error checking free space: {}", e),
+            }
+            state.record_error(&e);
+            return;
+        }
+    };
+    let start = now;
+    log::info!("{} candidates, at {}, deleting until {}", candidates.len(), goal.format(now), goal.format(target));
+    let mut freed = 0;
+    let mut since_check = 0;
+    for candidate in &candidates {
+        if goal.reached(now) {
+            break;
+        }
+        if options.verbose { println!("deleting {}", candidate.path.display()); }
+        if options.dryrun {
+            if options.verbose { println!("(dryrun) did nothing"); }
+            // pretend it worked so the dry run stops where the real one would
+            now = goal.after(now, goal.gained(&candidate.stat, options));
+            freed += goal.gained(&candidate.stat, options);
+            state.record_deleted(&candidate.path, &candidate.stat, options);
+            state.record_project(candidate.project, &candidate.stat, options);
+            continue;
+        }
+        if let Err(e) = delete_candidate(candidate, options) {
+            println!("error deleting {}: {}", candidate.path.display(), e);
+            state.record_failed(&candidate.path, &e);
+            continue;
+        }
+        freed += goal.gained(&candidate.stat, options);
+        state.record_deleted(&candidate.path, &candidate.stat, options);
+        state.record_project(candidate.project, &candidate.stat, options);
+        since_check += 1;
+        // blocks aren't always given back straight away, so guess in between real checks
+        now = goal.after(now, goal.gained(&candidate.stat, options));
+        if since_check >= RECHECK_EVERY || goal.reached(now) {
+            since_check = 0;
+            if let Ok(actual) = goal.measure(&options.starting_dir) {
+                log::debug!("at {}, guessed {}", goal.format(actual), goal.format(now));
+                now = actual;
+            }
+        }
+    }
+
+    if !options.dryrun {
+        now = goal.measure(&options.starting_dir).unwrap_or(now);
+    }
+    match goal {
+        Goal::Project { id, .. } => {
+            println!("freed {}, project {} now uses {} (was {})", goal.format(freed), id, goal.format(now), goal.format(start));
+            if !goal.reached(now) {
+                println!("ran out of files to delete before project {} was under {}", id, goal.format(target));
+            }
+        }
+        _ => {
+            println!("freed {}, {} now free (was {})", goal.format(freed), goal.format(now), goal.format(start));
+            if !goal.reached(now) {
+                println!("ran out of files to delete before reaching {} free", goal.format(target));
+            }
+        }
+    }
+}
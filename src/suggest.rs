@@ -0,0 +1,71 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+/// levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + if ca == *cb { 0 } else { 1 };
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// entries of a directory, sorted by how close their name is to `name`
+fn closest_entries(dir: &Path, name: &str) -> Vec<(usize, OsString)> {
+    let mut entries: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.flatten()
+            .map(|e| (edit_distance(name, &e.file_name().to_string_lossy()), e.file_name()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    entries
+}
+
+/// prints suggestions for a path that doesn't exist, by walking up to the nearest existing
+/// parent and then trying to fix each missing component with the closest sibling
+pub fn suggest(path: &Path) {
+    let mut existing = path.to_path_buf();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                missing.push(name.to_os_string());
+                existing = parent.to_path_buf();
+            }
+            _ => return,
+        }
+    }
+    missing.reverse();
+
+    // try to correct each missing component in turn
+    let mut corrected = Some(existing.clone());
+    for name in &missing {
+        corrected = corrected.and_then(|dir| {
+            let name = name.to_string_lossy();
+            match closest_entries(&dir, &name).into_iter().next() {
+                // don't suggest something that's barely related
+                Some((distance, entry)) if distance <= (name.chars().count() / 3).max(2) => Some(dir.join(entry)),
+                _ => None,
+            }
+        });
+    }
+    if let Some(corrected) = corrected.filter(|c| c.exists()) {
+        println!("did you mean {}?", corrected.display());
+        return;
+    }
+
+    println!("closest existing parent is {}", existing.display());
+    let siblings = closest_entries(&existing, &missing[0].to_string_lossy());
+    if !siblings.is_empty() {
+        let names: Vec<_> = siblings.iter().take(5).map(|(_, n)| n.to_string_lossy()).collect();
+        println!("  which contains: {}", names.join(", "));
+    }
+}
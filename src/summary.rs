@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::{format_size, RunState};
+
+/// how the end of run report is written out
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Option<Format> {
+        match format {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// the exit codes for how much a run freed, for `--summary-exit-code-from-freed-bytes`.
+/// they're out of the way of the error codes, which win over them
+pub const FREED_NOTHING: i32 = 20;
+pub const FREED_UNDER_1G: i32 = 21;
+pub const FREED_1G_OR_MORE: i32 = 22;
+
+/// which of the codes above a run that freed `bytes` exits with
+pub fn freed_exit_code(bytes: u64) -> i32 {
+    match bytes {
+        0 => FREED_NOTHING,
+        b if b < 1 << 30 => FREED_UNDER_1G,
+        _ => FREED_1G_OR_MORE,
+    }
+}
+
+/// how wide the longest bar of `histogram` is
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// the skip counts as a bar chart, most common reason first, for
+/// `--report-skipped-reasons-histogram`
+pub fn histogram(skipped: &BTreeMap<&'static str, usize>) -> String {
+    let total: usize = skipped.values().sum();
+    if total == 0 {
+        return "nothing was skipped\n".into();
+    }
+    let mut reasons: Vec<_> = skipped.iter().collect();
+    reasons.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    let most = *reasons[0].1;
+    let name_width = reasons.iter().map(|(reason, _)| reason.len()).max().unwrap_or(0);
+    let count_width = most.to_string().len();
+    let mut text = format!("skipped {}, by reason:\n", total);
+    for (reason, &n) in reasons {
+        // anything skipped at all gets some bar, however much more common the top reason is
+        let bar = (n * HISTOGRAM_WIDTH).div_ceil(most);
+        text.push_str(&format!("  {:<name_width$} {:>count_width$} {}\n", reason, n, "#".repeat(bar)));
+    }
+    text
+}
+
+/// what a run did, for `--report-format`
+pub struct Summary {
+    pub files_deleted: usize,
+    pub dirs_removed: usize,
+    pub bytes_freed: u64,
+    /// how many things were skipped, by `SkipReason::name`
+    pub skipped: BTreeMap<&'static str, usize>,
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    pub fn new(state: &RunState, elapsed: Duration) -> Summary {
+        Summary {
+            files_deleted: state.files_deleted,
+            dirs_removed: state.dirs_removed,
+            bytes_freed: state.bytes_freed,
+            skipped: state.skipped.clone(),
+            errors: state.errors,
+            elapsed,
+        }
+    }
+
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.text(),
+            Format::Json => self.json(),
+            Format::Csv => self.csv(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "deleted {} files and {} dirs, freeing {}, in {:.1}s\n",
+            self.files_deleted, self.dirs_removed, format_size(self.bytes_freed), self.elapsed.as_secs_f64(),
+        );
+        let skipped: usize = self.skipped.values().sum();
+        text.push_str(&format!("skipped {}", skipped));
+        if skipped > 0 {
+            let reasons: Vec<_> = self.skipped.iter().map(|(reason, n)| format!("{} {}", n, reason)).collect();
+            text.push_str(&format!(" ({})", reasons.join(", ")));
+        }
+        text.push_str(&format!(", {} errors", self.errors));
+        text
+    }
+
+    /// a single json object. the reasons are all plain identifiers, so nothing needs escaping
+    pub fn json(&self) -> String {
+        let skipped: Vec<_> = self.skipped.iter().map(|(reason, n)| format!("\"{}\":{}", reason, n)).collect();
+        format!(
+            "{{\"files_deleted\":{},\"dirs_removed\":{},\"bytes_freed\":{},\"skipped\":{{{}}},\"errors\":{},\"elapsed_secs\":{:.3}}}",
+            self.files_deleted, self.dirs_removed, self.bytes_freed, skipped.join(","), self.errors, self.elapsed.as_secs_f64(),
+        )
+    }
+
+    /// a single row with no header so it can be appended to a log, the columns being
+    /// files_deleted, dirs_removed, bytes_freed, skipped, skipped_by_reason, errors, elapsed_secs.
+    /// skipped_by_reason is `reason=n` pairs separated by `;`
+    pub fn csv(&self) -> String {
+        let skipped: usize = self.skipped.values().sum();
+        let reasons: Vec<_> = self.skipped.iter().map(|(reason, n)| format!("{}={}", reason, n)).collect();
+        format!(
+            "{},{},{},{},{},{},{:.3}",
+            self.files_deleted, self.dirs_removed, self.bytes_freed, skipped, reasons.join(";"), self.errors, self.elapsed.as_secs_f64(),
+        )
+    }
+}
@@ -0,0 +1,60 @@
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::dirfd::Dir;
+
+/// swaps the directory at `path` for a new empty one with the same permissions, returning
+/// where the old one ended up, next to it in the same parent. the swap happens in one go,
+/// so anything using the directory sees it go from full to empty and never missing
+pub fn swap_empty(path: &Path) -> io::Result<PathBuf> {
+    let parent = path.parent().ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let name = path.file_name().ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let dir = Dir::open(parent)?;
+    let metadata = std::fs::symlink_metadata(path)?;
+
+    // not hidden, or deleting it would skip it as a hidden file
+    let mut aside_name = name.to_os_string();
+    aside_name.push(format!(".saferm-swap-{}", std::process::id()));
+    let aside = parent.join(&aside_name);
+    std::fs::create_dir(&aside)?;
+    // set afterwards so the umask doesn't get in the way, only root can give it the same owner
+    if let Err(e) = std::fs::set_permissions(&aside, metadata.permissions()) {
+        let _ = std::fs::remove_dir(&aside);
+        return Err(e);
+    }
+    let _ = std::os::unix::fs::lchown(&aside, Some(metadata.uid()), Some(metadata.gid()));
+
+    if let Err(e) = dir.exchange(name, &aside_name) {
+        let _ = std::fs::remove_dir(&aside);
+        return Err(e);
+    }
+    Ok(aside)
+}
+
+/// moves whatever's left in the swapped aside directory back into `path`, since it was only
+/// left because it was skipped, then removes the aside directory if that emptied it
+pub fn restore_leftovers(aside: &Path, path: &Path) -> io::Result<()> {
+    let names: Vec<OsString> = match std::fs::read_dir(aside) {
+        Ok(entries) => entries.flatten().map(|e| e.file_name()).collect(),
+        // it was deleted along with everything in it
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in &names {
+        let destination = path.join(name);
+        // something new has shown up under the same name since the swap, don't clobber it
+        if destination.symlink_metadata().is_ok() {
+            println!("warning: {} already exists, leaving it in {}", destination.display(), aside.display());
+            continue;
+        }
+        std::fs::rename(aside.join(name), &destination)?;
+    }
+    if !names.is_empty() {
+        println!("moved {} skipped entries back into {}", names.len(), path.display());
+    }
+    match std::fs::remove_dir(aside) {
+        Err(e) if e.raw_os_error() == Some(libc::ENOTEMPTY) => Ok(()),
+        other => other,
+    }
+}
@@ -0,0 +1,57 @@
+use std::ffi::CString;
+use std::path::Path;
+
+/// sends what a run did to the system log, for `--syslog`
+pub struct Syslog {
+    priority: libc::c_int,
+    /// also send a line for everything deleted, not just the summary at the end
+    pub each: bool,
+    // openlog keeps the pointer, so this has to live as long as we log
+    _tag: CString,
+}
+
+impl Syslog {
+    /// parses a priority name, like `logger -p` takes without the facility
+    pub fn parse_priority(priority: &str) -> Option<libc::c_int> {
+        match priority {
+            "emerg" => Some(libc::LOG_EMERG),
+            "alert" => Some(libc::LOG_ALERT),
+            "crit" => Some(libc::LOG_CRIT),
+            "err" | "error" => Some(libc::LOG_ERR),
+            "warning" | "warn" => Some(libc::LOG_WARNING),
+            "notice" => Some(libc::LOG_NOTICE),
+            "info" => Some(libc::LOG_INFO),
+            "debug" => Some(libc::LOG_DEBUG),
+            _ => None,
+        }
+    }
+
+    /// opens the log with `tag` in front of every line, as the user facility. this can only
+    /// be done once per process, openlog has nowhere else to keep it
+    pub fn open(tag: &str, priority: libc::c_int, each: bool) -> Option<Syslog> {
+        let tag = CString::new(tag).ok()?;
+        unsafe { libc::openlog(tag.as_ptr(), libc::LOG_PID, libc::LOG_USER) };
+        Some(Syslog { priority, each, _tag: tag })
+    }
+
+    pub fn send(&self, message: &str) {
+        // a nul would end the message early, and syslog would take a % as a format
+        let Ok(message) = CString::new(message.replace('\0', " ")) else {
+            return;
+        };
+        unsafe { libc::syslog(self.priority, c"%s".as_ptr(), message.as_ptr()) };
+    }
+
+    /// a line for something that was removed, with `--syslog-each`
+    pub fn removed(&self, path: &Path, action: &str) {
+        if self.each {
+            self.send(&format!("{} {}", action, path.display()));
+        }
+    }
+}
+
+impl Drop for Syslog {
+    fn drop(&mut self) {
+        unsafe { libc::closelog() };
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use crate::{delete, leftovers, DeleteOptions, RunState};
+
+/// deletes what the symlinks we removed pointed to, for `--with-targets`. only targets inside
+/// the starting directory go, and only if no symlink we left behind points at them too. this
+/// is best effort: a link from outside the starting directory, or one made while we were
+/// running, isn't looked for. the targets go through the same checks as anything else
+pub fn delete_orphaned_targets(options: &DeleteOptions, state: &mut RunState) {
+    let targets = std::mem::take(&mut state.link_targets);
+    if targets.is_empty() {
+        return;
+    }
+    let Ok(start) = options.starting_dir.canonicalize() else {
+        return;
+    };
+    let removed: HashSet<&PathBuf> = targets.iter().map(|(link, _)| link).collect();
+    // in a dry run the links we'd have removed are still there, so they don't count
+    let still_linked: HashSet<PathBuf> = leftovers(&start).into_iter()
+        .filter(|path| path.is_symlink() && !removed.contains(path))
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    let mut done = HashSet::new();
+    for (link, target) in &targets {
+        if !target.starts_with(&start) || !done.insert(target) {
+            continue;
+        }
+        if still_linked.contains(target) {
+            println!("{} is still linked to from somewhere else, keeping it", target.display());
+            continue;
+        }
+        if target.symlink_metadata().is_err() {
+            continue;
+        }
+        println!("{} pointed to {}, deleting it too", link.display(), target.display());
+        delete(&target.to_string_lossy(), options, state);
+    }
+}
@@ -0,0 +1,692 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::dirfd::{Dir, Stat};
+use crate::reflink::Reflink;
+
+/// a freedesktop.org trash directory (`files/` and `info/` subdirectories)
+pub struct Trash {
+    pub dir: PathBuf,
+    /// the most we're allowed to have in the trash at once, in bytes
+    pub max_size: Option<u64>,
+    /// purge the oldest trashed items to make room instead of deleting permanently
+    pub purge_oldest: bool,
+    /// record permissions, owner and mtime in the `.trashinfo` too
+    pub extra: bool,
+    /// keep every generation of a name under `files/<name>/<version>`, for `--trash-versioned`
+    pub versioned: bool,
+    /// how to copy files when the trash is on another device
+    pub reflink: Reflink,
+    /// copies across devices happen here instead of holding up the walk
+    pub copier: Option<crate::copier::Copier>,
+    /// what to do with a file when there's no room left for it in the trash, for `--trash-fallback`
+    pub fallback: Fallback,
+    /// delete a file for good if the trash already has one from the same path that's no older,
+    /// for `--skip-if-newer-in-trash`
+    pub skip_if_newer: bool,
+    /// gzip files on the way into the trash, for `--trash-compress`
+    pub compress: bool,
+    /// how many bytes are currently in the trash
+    used: Cell<u64>,
+    /// the newest modification time of what's in the trash from each original path, in
+    /// nanoseconds since the epoch. only read the first time it's needed
+    newest: RefCell<Option<HashMap<PathBuf, i128>>>,
+}
+
+/// what happens to a file that can't be trashed because the disk the trash is on is full
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Fallback {
+    /// delete it for good, which frees its space straight away
+    Permanent,
+    /// stop the run
+    Abort,
+    /// leave it where it is and report it
+    Keep,
+}
+
+impl Fallback {
+    pub fn parse(fallback: &str) -> Option<Fallback> {
+        match fallback {
+            "permanent" => Some(Fallback::Permanent),
+            "abort" => Some(Fallback::Abort),
+            "keep" => Some(Fallback::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// what trashing fails with when the trash is full and `--trash-fallback abort` says to stop
+#[derive(Debug)]
+pub struct Full;
+
+impl std::fmt::Display for Full {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the trash is full")
+    }
+}
+
+impl std::error::Error for Full {}
+
+/// returns true if `e` means the run should stop because the trash is full
+pub fn is_full(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<Full>())
+}
+
+/// something that's in the trash, as listed by `Trash::entries`
+pub struct TrashEntry {
+    /// its name in `files/`, and in `info/` with `.trashinfo` added. versioned entries are
+    /// `<name>/<version>`
+    pub name: String,
+    pub info: TrashInfo,
+}
+
+/// what happened when we tried to put something in the trash
+pub enum Trashed {
+    /// the file was moved into the trash
+    Moved,
+    /// the file wouldn't fit in the trash budget, so it was deleted permanently
+    DeletedPermanently,
+    /// the trash already had a copy from the same path that was no older, so it was deleted
+    /// permanently instead of being trashed again
+    Redundant,
+}
+
+/// returns the home trash directory, `$XDG_DATA_HOME/Trash` or `~/.local/share/Trash`
+pub fn home_trash_dir() -> Option<PathBuf> {
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(data_home).join("Trash"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/Trash"))
+}
+
+/// checks that a trash directory given with `--trash-dir` is a directory we can write to
+pub fn check_trash_dir(dir: &Path) -> io::Result<()> {
+    if !std::fs::metadata(dir)?.is_dir() {
+        return Err(io::Error::other(format!("{} is not a directory", dir.display())));
+    }
+    if !crate::permissions::access(dir, libc::W_OK) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is not writable", dir.display())));
+    }
+    Ok(())
+}
+
+/// returns the total size of a file or directory, without following symlinks
+pub fn total_size(path: &Path) -> u64 {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    let mut size = 0;
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            size += total_size(&entry.path());
+        }
+    }
+    size
+}
+
+/// percent-encodes a path for the `Path` key of a `.trashinfo` file
+fn url_encode(path: &Path) -> String {
+    let mut out = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// decodes a percent-encoded `Path` value back into a path
+fn url_decode(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(b) = encoded.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(out))
+}
+
+/// what a file was like before it was trashed, kept in a `[saferm]` section of its
+/// `.trashinfo` with `--trash-info-extra` so restoring it can put everything back
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Extra {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// the modification time, in nanoseconds since the epoch
+    pub mtime: i128,
+}
+
+impl Extra {
+    pub fn of(stat: &Stat) -> Extra {
+        Extra { mode: stat.mode(), uid: stat.uid(), gid: stat.gid(), mtime: nanos(stat.modified()) }
+    }
+
+    /// gives a restored file back its permissions, owner and modification time, without
+    /// following it if it's a symlink. only root can give it back to another user
+    pub fn apply(&self, path: &Path) -> io::Result<()> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        let _ = std::os::unix::fs::lchown(path, Some(self.uid), Some(self.gid));
+        // symlinks don't have permissions of their own
+        if !metadata.file_type().is_symlink() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(self.mode))?;
+        }
+        let path_cstr = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+        let mtime = libc::timespec {
+            tv_sec: self.mtime.div_euclid(1_000_000_000) as libc::time_t,
+            tv_nsec: self.mtime.rem_euclid(1_000_000_000) as libc::c_long,
+        };
+        // leave the access time alone
+        let times = [libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_OMIT }, mtime];
+        if unsafe { libc::utimensat(libc::AT_FDCWD, path_cstr.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// `time` in nanoseconds since the epoch, negative if it's before
+fn nanos(time: std::time::SystemTime) -> i128 {
+    match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    }
+}
+
+/// the contents of a `.trashinfo` file
+pub struct TrashInfo {
+    /// where the file was before it was trashed
+    pub path: PathBuf,
+    /// when it was trashed, as `YYYY-MM-DDThh:mm:ss` local time
+    pub deletion_date: String,
+    /// what the file was like, if we recorded it
+    pub extra: Option<Extra>,
+    /// which generation of its name this is, with `--trash-versioned`
+    pub version: Option<u32>,
+    /// the file in the trash is gzipped, with `--trash-compress`
+    pub compressed: bool,
+}
+
+impl TrashInfo {
+    /// parses a `.trashinfo` file, whether we wrote it or another trash tool did
+    pub fn parse(contents: &str) -> Option<TrashInfo> {
+        let mut section = "";
+        let mut path = None;
+        let mut deletion_date = None;
+        let (mut mode, mut uid, mut gid, mut mtime, mut version) = (None, None, None, None, None);
+        let mut compressed = false;
+        for line in contents.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.starts_with('[') {
+                section = line;
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            match (section, key) {
+                ("[Trash Info]", "Path") => path = Some(url_decode(value)),
+                ("[Trash Info]", "DeletionDate") => deletion_date = Some(value.to_string()),
+                ("[saferm]", "Mode") => mode = u32::from_str_radix(value, 8).ok(),
+                ("[saferm]", "Uid") => uid = value.parse().ok(),
+                ("[saferm]", "Gid") => gid = value.parse().ok(),
+                ("[saferm]", "Mtime") => mtime = value.parse().ok(),
+                ("[saferm]", "Version") => version = value.parse().ok(),
+                ("[saferm]", "Compression") => compressed = value == "gzip",
+                _ => {}
+            }
+        }
+        // only use the extra section if none of it is missing
+        let extra = match (mode, uid, gid, mtime) {
+            (Some(mode), Some(uid), Some(gid), Some(mtime)) => Some(Extra { mode, uid, gid, mtime }),
+            _ => None,
+        };
+        Some(TrashInfo { path: path?, deletion_date: deletion_date.unwrap_or_default(), extra, version, compressed })
+    }
+
+    /// formats this as a `.trashinfo` file. the `[Trash Info]` section comes first and is
+    /// exactly what the spec asks for, so other trash tools can still read it
+    fn to_file_contents(&self) -> String {
+        let mut contents = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", url_encode(&self.path), self.deletion_date);
+        if self.extra.is_some() || self.version.is_some() || self.compressed {
+            contents.push_str("\n[saferm]\n");
+        }
+        if let Some(extra) = &self.extra {
+            contents.push_str(&format!(
+                "Mode={:04o}\nUid={}\nGid={}\nMtime={}\n",
+                extra.mode, extra.uid, extra.gid, extra.mtime,
+            ));
+        }
+        if let Some(version) = self.version {
+            contents.push_str(&format!("Version={}\n", version));
+        }
+        if self.compressed {
+            contents.push_str("Compression=gzip\n");
+        }
+        contents
+    }
+}
+
+/// the current local time formatted as the spec wants it, `YYYY-MM-DDThh:mm:ss`
+fn deletion_date() -> String {
+    use libc::*;
+    let mut tm = MaybeUninit::<tm>::uninit();
+    unsafe {
+        let now = time(std::ptr::null_mut());
+        localtime_r(&now, tm.as_mut_ptr());
+        let tm = tm.assume_init();
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday,
+                tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
+
+impl Trash {
+    /// opens (and creates if needed) a trash directory, summing up what's already in it
+    pub fn open(dir: PathBuf, max_size: Option<u64>, purge_oldest: bool) -> io::Result<Trash> {
+        std::fs::create_dir_all(dir.join("files"))?;
+        std::fs::create_dir_all(dir.join("info"))?;
+        let used = Cell::new(total_size(&dir.join("files")));
+        log::info!("using the trash at {}, {} bytes already in it", dir.display(), used.get());
+        Ok(Trash { dir, max_size, purge_oldest, extra: false, versioned: false, reflink: Reflink::Never, copier: None, fallback: Fallback::Keep, skip_if_newer: false, compress: false, used, newest: RefCell::default() })
+    }
+
+    /// moves the file `name` in `dir` (which lives at `path`) into the trash,
+    /// writing its `.trashinfo` first as the spec asks. if the disk the trash is on is full,
+    /// `fallback` says what happens instead
+    pub fn trash(&self, dir: &Dir, name: &OsStr, path: &Path, stat: &Stat) -> io::Result<Trashed> {
+        match self.trash_inner(dir, name, path, stat) {
+            Err(e) if e.kind() == io::ErrorKind::StorageFull => match self.fallback {
+                Fallback::Permanent => {
+                    println!("warning: the trash is full, deleting {} permanently", path.display());
+                    unlink_if_same(dir, name, stat)?;
+                    Ok(Trashed::DeletedPermanently)
+                }
+                Fallback::Abort => Err(io::Error::new(io::ErrorKind::StorageFull, Full)),
+                Fallback::Keep => Err(e),
+            },
+            other => other,
+        }
+    }
+
+    fn trash_inner(&self, dir: &Dir, name: &OsStr, path: &Path, stat: &Stat) -> io::Result<Trashed> {
+        let size = stat.size();
+        let original = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+        if self.skip_if_newer && self.newest_from(&original)?.is_some_and(|newest| nanos(stat.modified()) <= newest) {
+            dir.unlink(name)?;
+            return Ok(Trashed::Redundant);
+        }
+        if let Some(max_size) = self.max_size {
+            if self.used.get() + size > max_size {
+                if self.purge_oldest {
+                    self.purge_until_fits(size, max_size)?;
+                }
+                if self.used.get() + size > max_size {
+                    println!("warning: {} would overflow the trash, deleting it permanently", path.display());
+                    dir.unlink(name)?;
+                    return Ok(Trashed::DeletedPermanently);
+                }
+            }
+        }
+
+        let mut file_name = original.file_name().unwrap_or("unnamed".as_ref()).to_string_lossy().into_owned();
+        let extra = self.extra.then(|| Extra::of(stat));
+        // only files, there's nothing in a symlink to compress
+        let compressed = self.compress && stat.is_file();
+        // so it looks like what it is to anything else going through the trash, the versions
+        // of a name are already in a directory of it
+        if compressed && !self.versioned {
+            file_name.push_str(".gz");
+        }
+        let mut info = TrashInfo { path: original.clone(), deletion_date: deletion_date(), extra, version: None, compressed };
+
+        // find a name that isn't taken yet, creating the info file exclusively to claim it
+        let mut n = if self.versioned { self.prepare_versions(&file_name)? } else { 1 };
+        let (trash_name, info_path) = loop {
+            let trash_name = match n {
+                _ if self.versioned => format!("{}/{}", file_name, n),
+                1 => file_name.clone(),
+                n => format!("{}.{}", file_name, n),
+            };
+            if self.versioned {
+                info.version = Some(n);
+            }
+            let info_path = self.dir.join("info").join(format!("{}.trashinfo", trash_name));
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+                // the versions of a name are kept in a directory of that name
+                Ok(_) if self.dir.join("files").join(&trash_name).symlink_metadata().is_ok() => {
+                    std::fs::remove_file(&info_path)?;
+                    n += 1;
+                }
+                Ok(mut file) => {
+                    use std::io::Write;
+                    if let Err(e) = file.write_all(info.to_file_contents().as_bytes()) {
+                        let _ = std::fs::remove_file(&info_path);
+                        return Err(e);
+                    }
+                    break (trash_name, info_path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e),
+            }
+        };
+
+        let destination = self.dir.join("files").join(&trash_name);
+        if compressed {
+            if let Err(e) = compress_file(dir, name, stat, &destination) {
+                let _ = std::fs::remove_file(&destination);
+                let _ = std::fs::remove_file(&info_path);
+                return Err(e);
+            }
+            let size = std::fs::symlink_metadata(&destination).map_or(size, |m| m.len());
+            self.used.set(self.used.get() + size);
+            log::debug!("trashed {} as {}, compressed", path.display(), destination.display());
+            return Ok(Trashed::Moved);
+        }
+        let moved = match &self.copier {
+            Some(copier) => match dir.rename_out(name, &destination) {
+                Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+                    let dir = dir.try_clone()?;
+                    let (name, from, stat, reflink, fallback) = (name.to_os_string(), path.to_path_buf(), *stat, self.reflink, self.fallback);
+                    log::debug!("{} is on another device than the trash, copying it in the background", path.display());
+                    copier.start(path.to_path_buf(), stat, move || {
+                        let copied = copy_file(&from, &stat, &destination, reflink).and_then(|_| unlink_if_same(&dir, &name, &stat));
+                        if copied.is_err() {
+                            let _ = std::fs::remove_file(&destination);
+                            let _ = std::fs::remove_file(&info_path);
+                        }
+                        // the walk has moved on by now, so there's no stopping it from here
+                        match copied {
+                            Err(e) if e.kind() == io::ErrorKind::StorageFull && fallback == Fallback::Permanent => {
+                                println!("warning: the trash is full, deleting {} permanently", from.display());
+                                unlink_if_same(&dir, &name, &stat)
+                            }
+                            other => other,
+                        }
+                    });
+                    self.used.set(self.used.get() + size);
+                    return Ok(Trashed::Moved);
+                }
+                other => other,
+            },
+            None => move_file(dir, name, path, stat, &destination, self.reflink),
+        };
+        if let Err(e) = moved {
+            let _ = std::fs::remove_file(&info_path);
+            return Err(e);
+        }
+        self.used.set(self.used.get() + size);
+        log::debug!("trashed {} as {}", path.display(), destination.display());
+        Ok(Trashed::Moved)
+    }
+
+    /// the modification time of the newest thing in the trash that came from `original`. the
+    /// one recorded with `--trash-info-extra` is used if there is one, since a copy made
+    /// across devices has the time it was copied
+    fn newest_from(&self, original: &Path) -> io::Result<Option<i128>> {
+        let mut newest = self.newest.borrow_mut();
+        if newest.is_none() {
+            let mut by_path: HashMap<PathBuf, i128> = HashMap::new();
+            for entry in self.entries()? {
+                let mtime = match entry.info.extra {
+                    Some(extra) => extra.mtime,
+                    None => match self.dir.join("files").join(&entry.name).symlink_metadata().and_then(|m| m.modified()) {
+                        Ok(modified) => nanos(modified),
+                        Err(_) => continue,
+                    },
+                };
+                let newest = by_path.entry(entry.info.path).or_insert(mtime);
+                *newest = (*newest).max(mtime);
+            }
+            *newest = Some(by_path);
+        }
+        Ok(newest.as_ref().and_then(|by_path| by_path.get(original).copied()))
+    }
+
+    /// makes the directories the versions of `name` go in, returning the version after the
+    /// newest one that's there
+    fn prepare_versions(&self, name: &str) -> io::Result<u32> {
+        // something trashed without versions has an info file of the same name
+        if self.dir.join("info").join(format!("{}.trashinfo", name)).exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is in the trash without versions", name)));
+        }
+        let mut newest = 0;
+        for dir in ["files", "info"] {
+            let versions = self.dir.join(dir).join(name);
+            match std::fs::create_dir(&versions) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !versions.is_dir() => {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is in the trash without versions", name)));
+                }
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+                _ => {}
+            }
+        }
+        for entry in std::fs::read_dir(self.dir.join("info").join(name))?.flatten() {
+            let version = entry.file_name().to_str().and_then(|n| n.strip_suffix(".trashinfo")).and_then(|n| n.parse().ok());
+            newest = newest.max(version.unwrap_or(0));
+        }
+        Ok(newest + 1)
+    }
+
+    /// every `.trashinfo` in the trash with the name of what it's for. the versions of things
+    /// trashed with `--trash-versioned` are one directory down, and named `<name>/<version>`
+    fn info_files(&self) -> io::Result<Vec<(String, std::fs::DirEntry)>> {
+        let mut info_files = Vec::new();
+        let strip = |entry: &std::fs::DirEntry| entry.file_name().to_str().and_then(|n| n.strip_suffix(".trashinfo")).map(str::to_string);
+        for entry in std::fs::read_dir(self.dir.join("info"))?.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                let Some(dir_name) = entry.file_name().to_str().map(str::to_string) else { continue };
+                for version in std::fs::read_dir(entry.path())?.flatten() {
+                    if let Some(name) = strip(&version) {
+                        info_files.push((format!("{}/{}", dir_name, name), version));
+                    }
+                }
+            } else if let Some(name) = strip(&entry) {
+                info_files.push((name, entry));
+            }
+        }
+        Ok(info_files)
+    }
+
+    /// lists everything in the trash that has a readable `.trashinfo`, oldest first
+    pub fn entries(&self) -> io::Result<Vec<TrashEntry>> {
+        let mut entries = Vec::new();
+        for (name, entry) in self.info_files()? {
+            let info_path = entry.path();
+            let info = match std::fs::read_to_string(&info_path).ok().and_then(|contents| TrashInfo::parse(&contents)) {
+                Some(info) => info,
+                None => continue,
+            };
+            // the date is only to the second, so go by when the info file was written for ties
+            let written = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+            entries.push((written, TrashEntry { name, info }));
+        }
+        // the dates sort lexicographically in chronological order
+        entries.sort_by(|(a_written, a), (b_written, b)| (&a.info.deletion_date, a_written).cmp(&(&b.info.deletion_date, b_written)));
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// moves a trashed file back to where it was, refusing to replace anything that's there
+    /// now. its `.trashinfo` only goes once the file is back
+    pub fn restore(&self, entry: &TrashEntry) -> io::Result<()> {
+        let from = self.dir.join("files").join(&entry.name);
+        let to = &entry.info.path;
+        if to.symlink_metadata().is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", to.display())));
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let moved = if entry.info.compressed {
+            decompress_file(&from, to).and_then(|_| std::fs::remove_file(&from))
+        } else {
+            std::fs::rename(&from, to)
+        };
+        match moved {
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+                let metadata = std::fs::symlink_metadata(&from)?;
+                if metadata.is_dir() {
+                    return Err(io::Error::other("can't move a directory back across devices"));
+                }
+                let parent = Dir::open(&self.dir.join("files"))?;
+                let stat = parent.stat_child(entry.name.as_ref())?;
+                copy_file(&from, &stat, to, self.reflink)?;
+                parent.unlink(entry.name.as_ref())?;
+            }
+            other => other?,
+        }
+        if let Some(extra) = &entry.info.extra {
+            if let Err(e) = extra.apply(to) {
+                println!("warning: couldn't put back the permissions, owner or mtime of {}: {}", to.display(), e);
+            }
+        }
+        std::fs::remove_file(self.dir.join("info").join(format!("{}.trashinfo", entry.name)))?;
+        self.remove_empty_versions(&entry.name);
+        Ok(())
+    }
+
+    /// removes the directories holding the versions of a name once the last one is gone
+    fn remove_empty_versions(&self, name: &str) {
+        if let Some((versions, _)) = name.split_once('/') {
+            // these fail if there's anything left in them, which is what we want
+            let _ = std::fs::remove_dir(self.dir.join("files").join(versions));
+            let _ = std::fs::remove_dir(self.dir.join("info").join(versions));
+        }
+    }
+
+    /// removes the oldest trashed items until `size` more bytes fit under `max_size`
+    fn purge_until_fits(&self, size: u64, max_size: u64) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for (name, entry) in self.info_files()? {
+            let info_path = entry.path();
+            let date = std::fs::read_to_string(&info_path).ok()
+                .and_then(|contents| TrashInfo::parse(&contents))
+                .map(|info| info.deletion_date)
+                .unwrap_or_default();
+            entries.push((date, name, info_path));
+        }
+        // the dates sort lexicographically in chronological order
+        entries.sort();
+        for (_, name, info_path) in entries {
+            if self.used.get() + size <= max_size {
+                break;
+            }
+            let file = self.dir.join("files").join(&name);
+            let freed = total_size(&file);
+            println!("purging {} from the trash to make room", name);
+            if file.is_dir() && !file.is_symlink() {
+                std::fs::remove_dir_all(&file)?;
+            } else if file.symlink_metadata().is_ok() {
+                std::fs::remove_file(&file)?;
+            }
+            std::fs::remove_file(&info_path)?;
+            self.remove_empty_versions(&name);
+            self.used.set(self.used.get().saturating_sub(freed));
+        }
+        Ok(())
+    }
+}
+
+/// copies a file somewhere else, or makes a new symlink with the same target if it's a symlink
+pub(crate) fn copy_file(from: &Path, stat: &Stat, to: &Path, reflink: Reflink) -> io::Result<()> {
+    if stat.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+    } else {
+        crate::reflink::copy(from, to, reflink)?;
+    }
+    #[cfg(all(target_os = "linux", feature = "selinux"))]
+    if let Err(e) = crate::selinux::copy_context(from, to) {
+        println!("warning: couldn't keep the selinux context of {}: {}", from.display(), e);
+    }
+    Ok(())
+}
+
+/// writes the file `name` in `dir` gzipped to `to`, with the same permissions, then unlinks it
+/// as long as it's still the file we compressed
+fn compress_file(dir: &Dir, name: &OsStr, stat: &Stat, to: &Path) -> io::Result<()> {
+    #[cfg(feature = "compress")]
+    {
+        let from = dir.open_child_file(name)?;
+        let to = std::fs::OpenOptions::new().write(true).create_new(true).mode(stat.mode() & 0o7777).open(to)?;
+        crate::compress::compress(from, to)?;
+        unlink_if_same(dir, name, stat)
+    }
+    #[cfg(not(feature = "compress"))]
+    {
+        let _ = (dir, name, stat, to);
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// puts the gzipped file `from` back at `to` as it was, with the same permissions
+fn decompress_file(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(feature = "compress")]
+    {
+        let mode = std::fs::symlink_metadata(from)?.mode();
+        let source = std::fs::File::open(from)?;
+        let file = std::fs::OpenOptions::new().write(true).create_new(true).mode(mode & 0o7777).open(to)?;
+        if let Err(e) = crate::compress::decompress(source, file) {
+            let _ = std::fs::remove_file(to);
+            return Err(e);
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "compress"))]
+    {
+        let _ = (from, to);
+        Err(io::Error::other("it was trashed compressed, and this saferm was built without the compress feature"))
+    }
+}
+
+/// unlinks `name` from `dir` once we've copied it, as long as it's still the file we copied
+fn unlink_if_same(dir: &Dir, name: &OsStr, stat: &Stat) -> io::Result<()> {
+    let now = dir.stat_child(name)?;
+    if now.dev() != stat.dev() || now.ino() != stat.ino() {
+        return Err(io::Error::other("file was replaced while we were copying it"));
+    }
+    dir.unlink(name)
+}
+
+/// renames a file, falling back to copying and removing it when crossing devices
+pub(crate) fn move_file(dir: &Dir, name: &OsStr, from: &Path, stat: &Stat, to: &Path, reflink: Reflink) -> io::Result<()> {
+    match dir.rename_out(name, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            log::debug!("{} is on another device than {}, copying instead", from.display(), to.display());
+            // a copy that ran out of room halfway is no use to anyone
+            if let Err(e) = copy_file(from, stat, to, reflink) {
+                let _ = std::fs::remove_file(to);
+                return Err(e);
+            }
+            // if it was swapped for another file while we copied it, that one isn't ours to remove
+            if let Err(e) = unlink_if_same(dir, name, stat) {
+                let _ = std::fs::remove_file(to);
+                return Err(e);
+            }
+            Ok(())
+        }
+        other => other,
+    }
+}
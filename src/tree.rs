@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::plan::{plan, PlannedAction};
+use crate::{format_size, DeleteOptions};
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// when to color things in
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Color {
+    /// only when writing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub fn parse(color: &str) -> Option<Color> {
+        match color {
+            "auto" => Some(Color::Auto),
+            "always" => Some(Color::Always),
+            "never" => Some(Color::Never),
+            _ => None,
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        match self {
+            Color::Auto => unsafe { libc::isatty(1) == 1 },
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+/// where sizes stop being small and start being large, for `--heatmap`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Heatmap {
+    pub small: u64,
+    pub large: u64,
+}
+
+impl Heatmap {
+    /// picks the thresholds from the sizes themselves, so whatever the tree holds the biggest
+    /// tenth of the files is red and the smallest half is green
+    pub fn adaptive(sizes: &[u64]) -> Heatmap {
+        let mut sizes = sizes.to_vec();
+        sizes.sort_unstable();
+        let at = |fraction: f64| sizes.get((sizes.len().saturating_sub(1) as f64 * fraction) as usize).copied().unwrap_or(0);
+        Heatmap { small: at(0.5), large: at(0.9) }
+    }
+
+    fn color(&self, size: u64) -> &'static str {
+        if size > self.large {
+            RED
+        } else if size > self.small {
+            YELLOW
+        } else {
+            GREEN
+        }
+    }
+}
+
+/// one line of the tree
+struct Line {
+    path: PathBuf,
+    depth: usize,
+    /// None for a directory, whose size is added up afterwards
+    size: Option<u64>,
+    note: Option<String>,
+}
+
+/// prints what a run would do as a tree, with the size each entry would free. directories
+/// count everything under them that would go. with `heatmap`, sizes are colored from green to
+/// red, using the thresholds given or ones picked from the tree if there aren't any
+pub fn print(path: &Path, options: &DeleteOptions, heatmap: Option<Option<Heatmap>>) {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut removed = HashSet::new();
+    let mut dir_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    // where each directory's line is, its reason for being kept comes after its contents
+    let mut dir_lines: HashMap<PathBuf, usize> = HashMap::new();
+    let depth_of = |p: &Path| p.strip_prefix(path).map_or(0, |rest| rest.components().count());
+    for action in plan(path, options) {
+        let (entry, size, note) = match action {
+            PlannedAction::Delete(entry) => {
+                let size = entry.symlink_metadata().map_or(0, |m| if m.is_file() { m.len() } else { 0 });
+                (entry, Some(size), None)
+            }
+            PlannedAction::Skip { path: entry, reason } => {
+                let note = format!("kept, {}", reason);
+                if let Some(&i) = dir_lines.get(&entry) {
+                    lines[i].note = Some(note);
+                    continue;
+                }
+                (entry, Some(0), Some(note))
+            }
+            PlannedAction::EnterDir(entry) => (entry, None, None),
+            PlannedAction::RemoveDir(entry) => {
+                removed.insert(entry);
+                continue;
+            }
+            PlannedAction::Unmount(entry) => (entry, Some(0), Some("unmounted".to_string())),
+            PlannedAction::Error { path: entry, error } => (entry, Some(0), Some(format!("error: {}", error))),
+        };
+        if let Some(size) = size.filter(|&s| s > 0) {
+            for ancestor in entry.ancestors().skip(1) {
+                *dir_sizes.entry(ancestor.to_path_buf()).or_default() += size;
+                if ancestor == path {
+                    break;
+                }
+            }
+        }
+        if size.is_none() {
+            dir_lines.insert(entry.clone(), lines.len());
+        }
+        lines.push(Line { depth: depth_of(&entry), path: entry, size, note });
+    }
+    // a directory that's entered but not removed in the end is left behind
+    for line in &mut lines {
+        if line.size.is_none() && line.note.is_none() && !removed.contains(&line.path) {
+            line.note = Some("left behind".to_string());
+        }
+    }
+
+    let size_of = |line: &Line| line.size.unwrap_or_else(|| dir_sizes.get(&line.path).copied().unwrap_or(0));
+    // going by files alone, since directories are as big as everything in them put together
+    let heatmap = heatmap.map(|h| h.unwrap_or_else(|| Heatmap::adaptive(&lines.iter().filter_map(|l| l.size).collect::<Vec<_>>())));
+    for line in &lines {
+        let name = if line.depth == 0 { line.path.display().to_string() } else { line.path.file_name().unwrap_or_default().to_string_lossy().into_owned() };
+        let slash = if line.size.is_none() { "/" } else { "" };
+        let size = format!("{:>10}", format_size(size_of(line)));
+        let size = match &heatmap {
+            Some(heatmap) => format!("{}{}{}", heatmap.color(size_of(line)), size, RESET),
+            None => size,
+        };
+        let note = line.note.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+        println!("{} {}{}{}{}", size, "  ".repeat(line.depth), name, slash, note);
+    }
+}
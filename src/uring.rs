@@ -0,0 +1,269 @@
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::dirfd::{check, cstr, Dir, Stat};
+
+/// how many unlinks go to the kernel at once
+pub const ENTRIES: u32 = 256;
+
+// from linux/io_uring.h, libc only has the syscall numbers
+const IORING_OP_UNLINKAT: u8 = 36;
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+const IORING_FEAT_SINGLE_MMAP: u32 = 1;
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// `struct io_uring_sqe`, with only what unlinkat uses named
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    unlink_flags: u32,
+    user_data: u64,
+    rest: [u64; 3],
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// a mapping of part of the ring, unmapped when it's dropped
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Mapping> {
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED | libc::MAP_POPULATE, fd, offset)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { ptr: ptr.cast(), len })
+    }
+
+    /// what's at `offset` bytes in
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+/// a file handed to the kernel to unlink, kept until it's done so its name stays put
+struct Pending {
+    dir: RawFd,
+    name: CString,
+    path: PathBuf,
+    stat: Stat,
+}
+
+impl Pending {
+    /// unlinks the file without the ring
+    fn unlink(&self) -> io::Result<()> {
+        check(unsafe { libc::unlinkat(self.dir, self.name.as_ptr(), 0) }).map(|_| ())
+    }
+}
+
+/// a file the kernel is done unlinking, and how that went
+pub struct Done {
+    pub path: PathBuf,
+    pub stat: Stat,
+    pub result: io::Result<()>,
+}
+
+/// an io_uring that unlinks files in batches, for `--io-uring`. files are queued up as the walk
+/// finds them and go to the kernel in one syscall once there's a ring's worth, or when `flush`
+/// is called, which has to happen before the directories they're in are closed
+pub struct Ring {
+    fd: OwnedFd,
+    sq_ring: Mapping,
+    /// the completion ring, `None` when it shares the submission ring's mapping
+    cq_ring: Option<Mapping>,
+    sqes: Mapping,
+    params: Params,
+    /// what's been queued, by the slot it went in
+    pending: Vec<Option<Pending>>,
+    queued: u32,
+    /// set once the kernel said it doesn't know unlinkat, which it does from linux 5.11
+    unsupported: bool,
+}
+
+impl Ring {
+    /// sets up a ring, this fails on kernels without io_uring or where it's been turned off
+    pub fn new(entries: u32) -> io::Result<Ring> {
+        let mut params = Params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * std::mem::size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+        let (sq_ring, cq_ring) = if params.features & IORING_FEAT_SINGLE_MMAP != 0 {
+            (Mapping::new(fd.as_raw_fd(), sq_len.max(cq_len), IORING_OFF_SQ_RING)?, None)
+        } else {
+            let sq_ring = Mapping::new(fd.as_raw_fd(), sq_len, IORING_OFF_SQ_RING)?;
+            (sq_ring, Some(Mapping::new(fd.as_raw_fd(), cq_len, IORING_OFF_CQ_RING)?))
+        };
+        let sqes = Mapping::new(fd.as_raw_fd(), params.sq_entries as usize * std::mem::size_of::<Sqe>(), IORING_OFF_SQES)?;
+        let pending = (0..params.sq_entries).map(|_| None).collect();
+        Ok(Ring { fd, sq_ring, cq_ring, sqes, params, pending, queued: 0, unsupported: false })
+    }
+
+    /// returns false if unlinks can't go through the ring, and have to be done the usual way
+    pub fn usable(&self) -> bool {
+        !self.unsupported
+    }
+
+    fn cq_ring(&self) -> &Mapping {
+        self.cq_ring.as_ref().unwrap_or(&self.sq_ring)
+    }
+
+    /// queues the file `name` in `dir` to be unlinked. if the ring is full the queue goes to
+    /// the kernel first, and what's done is returned
+    pub fn unlink(&mut self, dir: &Dir, name: &OsStr, path: PathBuf, stat: Stat) -> io::Result<Vec<Done>> {
+        let name = cstr(name)?;
+        let done = if self.queued == self.params.sq_entries { self.flush() } else { Vec::new() };
+        let off = &self.params.sq_off;
+        let mask = unsafe { *self.sq_ring.at::<u32>(off.ring_mask) };
+        let tail = unsafe { &*self.sq_ring.at::<AtomicU32>(off.tail) };
+        let index = tail.load(Ordering::Relaxed) & mask;
+        let sqe = Sqe {
+            opcode: IORING_OP_UNLINKAT,
+            fd: dir.as_raw_fd(),
+            addr: name.as_ptr() as u64,
+            user_data: index as u64,
+            ..Default::default()
+        };
+        unsafe {
+            self.sqes.at::<Sqe>(0).add(index as usize).write(sqe);
+            *self.sq_ring.at::<u32>(off.array).add(index as usize) = index;
+        }
+        // the kernel only looks at the entry once it sees the new tail
+        tail.store(tail.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+        self.pending[index as usize] = Some(Pending { dir: dir.as_raw_fd(), name, path, stat });
+        self.queued += 1;
+        Ok(done)
+    }
+
+    /// hands everything queued to the kernel, waits for all of it and returns how it went
+    pub fn flush(&mut self) -> Vec<Done> {
+        let mut done = Vec::with_capacity(self.queued as usize);
+        while self.queued > 0 {
+            let submitted = unsafe {
+                libc::syscall(libc::SYS_io_uring_enter, self.fd.as_raw_fd(), self.queued, self.queued, IORING_ENTER_GETEVENTS, std::ptr::null::<libc::sigset_t>(), 0)
+            };
+            if submitted < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                // whatever the kernel didn't get to is still ours to unlink
+                log::warn!("io_uring stopped working, unlinking the usual way: {}", e);
+                self.unsupported = true;
+                for pending in self.pending.iter_mut().filter_map(Option::take) {
+                    let result = pending.unlink();
+                    done.push(Done { path: pending.path, stat: pending.stat, result });
+                }
+                self.queued = 0;
+                break;
+            }
+            self.reap(&mut done);
+        }
+        done
+    }
+
+    /// takes what's finished off the completion ring
+    fn reap(&mut self, done: &mut Vec<Done>) {
+        let off = &self.params.cq_off;
+        let ring = self.cq_ring();
+        let mask = unsafe { *ring.at::<u32>(off.ring_mask) };
+        let head = unsafe { &*ring.at::<AtomicU32>(off.head) };
+        let tail = unsafe { &*ring.at::<AtomicU32>(off.tail) };
+        let cqes = ring.at::<Cqe>(off.cqes);
+        let mut at = head.load(Ordering::Relaxed);
+        let mut finished = Vec::new();
+        while at != tail.load(Ordering::Acquire) {
+            let cqe = unsafe { cqes.add((at & mask) as usize).read() };
+            finished.push((cqe.user_data as usize, cqe.res));
+            at = at.wrapping_add(1);
+        }
+        head.store(at, Ordering::Release);
+        for (index, res) in finished {
+            let Some(pending) = self.pending[index].take() else { continue };
+            self.queued -= 1;
+            let result = match res {
+                // an old kernel that doesn't know the operation, so do it the usual way from here on
+                res if res == -libc::EINVAL => {
+                    self.unsupported = true;
+                    pending.unlink()
+                }
+                res if res < 0 => Err(io::Error::from_raw_os_error(-res)),
+                _ => Ok(()),
+            };
+            done.push(Done { path: pending.path, stat: pending.stat, result });
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::io;
+use std::path::Path;
+use crate::{DeleteOptions, RunState, Start};
+
+/// the second pass of `--two-phase`: goes back over everything the first pass removed, tries
+/// once more on anything that's still there and reports whatever survives that
+pub fn verify_removed(options: &DeleteOptions, state: &mut RunState) {
+    let removed = std::mem::take(&mut state.removed);
+    let mut retried = 0;
+    let mut stragglers = 0;
+    for path in &removed {
+        if path.symlink_metadata().is_err() {
+            continue;
+        }
+        println!("{} is still there, trying again", path.display());
+        retried += 1;
+        let result = retry(path, options).and_then(|_| match path.symlink_metadata() {
+            Ok(_) => Err(io::Error::other("it's still there")),
+            Err(_) => Ok(()),
+        });
+        if let Err(e) = result {
+            println!("error deleting {}: {}", path.display(), e);
+            state.record_failed(path, &e);
+            stragglers += 1;
+        }
+    }
+    println!("checked {} removals, {} needed another try, {} are still there", removed.len(), retried, stragglers);
+}
+
+fn retry(path: &Path, options: &DeleteOptions) -> io::Result<()> {
+    let Some(start) = Start::open(path)? else {
+        return Ok(());
+    };
+    if start.stat.is_dir() {
+        start.dir.remove_dir(start.name)
+    } else {
+        crate::remove_file(&start.dir, start.name, path, &start.stat, options)
+    }
+}
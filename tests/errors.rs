@@ -0,0 +1,33 @@
+mod common;
+
+use std::process::Command;
+use common::scratch_dir;
+
+#[test]
+fn failed_removals_keep_going_and_set_the_exit_code() {
+    let dir = scratch_dir("simulated");
+    let start = dir.join("start");
+    std::fs::create_dir(&start).unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::write(start.join(name), "").unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(["-r", "--simulate-error", "1", start.to_str().unwrap()])
+        .status()
+        .unwrap();
+    // an error that isn't one of the known kinds
+    assert_eq!(status.code(), Some(6));
+    for name in ["a", "b", "c"] {
+        assert!(start.join(name).exists());
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(["-r", "--simulate-error", "0", start.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!start.exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,27 @@
+use std::path::Path;
+use saferm::filter::{compile, Filters, Normalization};
+use saferm::SkipReason;
+
+/// which of `names` the filters would leave alone
+fn kept(filters: &Filters, names: &[&str]) -> Vec<String> {
+    names.iter().filter(|name| filters.check(name.as_ref(), Path::new(name), 1).is_some()).map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn ignore_case_matches_mixed_case_names() {
+    let names = ["app.log", "APP.LOG", "Old.Log", "notes.txt", "README.TXT"];
+
+    let filters = Filters { regex: Some(compile(r"\.log$", Normalization::None, false).unwrap()), ..Default::default() };
+    assert_eq!(kept(&filters, &names), ["APP.LOG", "Old.Log", "notes.txt", "README.TXT"]);
+
+    let filters = Filters { regex: Some(compile(r"\.log$", Normalization::None, true).unwrap()), ..Default::default() };
+    assert_eq!(kept(&filters, &names), ["notes.txt", "README.TXT"]);
+
+    let filters = Filters {
+        exclude_regex: Some(compile(r"^readme", Normalization::None, true).unwrap()),
+        include_at_depth: Some((1, compile(r"\.TXT$", Normalization::None, true).unwrap())),
+        ..Default::default()
+    };
+    assert_eq!(kept(&filters, &names), ["app.log", "APP.LOG", "Old.Log", "README.TXT"]);
+    assert!(matches!(filters.check("ReadMe.txt".as_ref(), Path::new("ReadMe.txt"), 1), Some(SkipReason::Excluded)));
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use common::scratch_dir;
+
+/// mounts a tmpfs on `at`, returns false if we aren't allowed to, so the test can be skipped
+fn mount_tmpfs(at: &Path) -> bool {
+    std::fs::create_dir_all(at).unwrap();
+    let mounted = Command::new("mount")
+        .args(["-t", "tmpfs", "saferm-test", at.to_str().unwrap()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    if !mounted {
+        eprintln!("couldn't mount a tmpfs on {}, skipping", at.display());
+    }
+    mounted
+}
+
+fn unmount(at: &Path) {
+    let _ = Command::new("umount").args(["-l", at.to_str().unwrap()]).status();
+}
+
+/// a tree with a busy mount in it and a file next to it, the file the mount is kept busy
+/// with is returned so it stays open for as long as the test needs it to
+fn busy_mount(name: &str) -> Option<(PathBuf, PathBuf, std::fs::File)> {
+    let dir = scratch_dir(name);
+    let start = dir.join("start");
+    let mount = start.join("mnt");
+    if !mount_tmpfs(&mount) {
+        std::fs::remove_dir_all(&dir).unwrap();
+        return None;
+    }
+    std::fs::write(start.join("file"), "").unwrap();
+    let busy = std::fs::File::create(mount.join("open")).unwrap();
+    Some((dir, mount, busy))
+}
+
+#[test]
+fn a_busy_mount_stops_the_run_with_abort() {
+    let Some((dir, mount, busy)) = busy_mount("unmount-abort") else { return };
+    // whether the file went first depends on the directory order, so only the mount is checked
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(["-r", "--on-mountpoint", "unmount", "--unmount-failure", "abort", dir.join("start").to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+    assert!(mount.join("open").exists());
+
+    drop(busy);
+    unmount(&mount);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_busy_mount_is_left_with_skip() {
+    let Some((dir, mount, busy)) = busy_mount("unmount-skip") else { return };
+    let start = dir.join("start");
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(["-r", "--on-mountpoint", "unmount", "--unmount-failure", "skip", start.to_str().unwrap()])
+        .status()
+        .unwrap();
+    // busy
+    assert_eq!(status.code(), Some(4));
+    assert!(mount.join("open").exists());
+    assert!(!start.join("file").exists());
+
+    drop(busy);
+    unmount(&mount);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// bind mounts a directory outside of the tree into it, returns None if we aren't allowed to
+fn bind_mount(name: &str) -> Option<(PathBuf, PathBuf, PathBuf)> {
+    let dir = scratch_dir(name);
+    let source = dir.join("source");
+    let mount = dir.join("start").join("bind");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&mount).unwrap();
+    std::fs::write(source.join("keep"), "").unwrap();
+    std::fs::write(dir.join("start").join("file"), "").unwrap();
+    let mounted = Command::new("mount")
+        .args(["--bind", source.to_str().unwrap(), mount.to_str().unwrap()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    if !mounted {
+        eprintln!("couldn't bind mount on {}, skipping", mount.display());
+        std::fs::remove_dir_all(&dir).unwrap();
+        return None;
+    }
+    Some((dir, source, mount))
+}
+
+#[test]
+fn bind_mounts_are_not_crossed() {
+    let Some((dir, source, mount)) = bind_mount("bind-skip") else { return };
+    let start = dir.join("start");
+    Command::new(env!("CARGO_BIN_EXE_saferm")).args(["-r", start.to_str().unwrap()]).status().unwrap();
+    assert!(source.join("keep").exists());
+    assert!(!start.join("file").exists());
+
+    unmount(&mount);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bind_mounts_are_unmounted_with_unmount() {
+    let Some((dir, source, mount)) = bind_mount("bind-unmount") else { return };
+    let start = dir.join("start");
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(["-r", "--on-mountpoint", "unmount", start.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert!(source.join("keep").exists());
+    // the directory it was bound on is left behind, like with any other mount
+    assert!(!mount.join("keep").exists());
+
+    unmount(&mount);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
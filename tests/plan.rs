@@ -0,0 +1,36 @@
+mod common;
+
+use saferm::plan::{plan, PlannedAction};
+use saferm::{DeleteOptions, SkipReason};
+use common::scratch_dir;
+
+#[test]
+fn plan_goes_through_the_same_checks_without_deleting() {
+    let dir = scratch_dir("plan");
+    let start = dir.join("start");
+    std::fs::create_dir_all(start.join("sub")).unwrap();
+    std::fs::write(start.join("file"), "").unwrap();
+    std::fs::write(start.join("sub").join("nested"), "").unwrap();
+    std::fs::write(start.join(".hidden"), "").unwrap();
+
+    let mut options = DeleteOptions::new(start.clone());
+    options.recursive = true;
+    let actions: Vec<_> = plan(&start, &options).collect();
+
+    let position = |wanted: &dyn Fn(&PlannedAction) -> bool| actions.iter().position(wanted).unwrap();
+    let enter_start = position(&|a| matches!(a, PlannedAction::EnterDir(p) if *p == start));
+    let enter_sub = position(&|a| matches!(a, PlannedAction::EnterDir(p) if *p == start.join("sub")));
+    let nested = position(&|a| matches!(a, PlannedAction::Delete(p) if *p == start.join("sub/nested")));
+    let remove_sub = position(&|a| matches!(a, PlannedAction::RemoveDir(p) if *p == start.join("sub")));
+    position(&|a| matches!(a, PlannedAction::Delete(p) if *p == start.join("file")));
+    position(&|a| matches!(a, PlannedAction::Skip { path, reason: SkipReason::Hidden } if *path == start.join(".hidden")));
+    // the hidden file stays, so the starting directory can't go either
+    let last = position(&|a| matches!(a, PlannedAction::Skip { path, reason: SkipReason::NotEmpty } if *path == start));
+    assert!(enter_start < enter_sub && enter_sub < nested && nested < remove_sub && remove_sub < last);
+    assert_eq!(last, actions.len() - 1);
+    assert_eq!(actions.len(), 7);
+
+    assert!(start.join("sub/nested").exists());
+    assert!(start.join("file").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
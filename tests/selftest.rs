@@ -0,0 +1,18 @@
+mod common;
+
+use std::process::Command;
+use common::scratch_dir;
+
+#[test]
+fn selftest_passes_and_cleans_up_after_itself() {
+    let dir = scratch_dir("selftest");
+    std::fs::write(dir.join("keep"), "").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_saferm")).args(["--selftest", dir.to_str().unwrap()]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(!stdout.contains("FAIL"));
+    let left: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(left, ["keep"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+use common::scratch_dir;
+
+fn saferm(data_home: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_saferm"))
+        .args(args)
+        .env("XDG_DATA_HOME", data_home)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// decodes the percent-encoding used by the `Path` key
+fn url_decode(encoded: &str) -> String {
+    let mut out = Vec::new();
+    let bytes = encoded.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            out.push(u8::from_str_radix(&encoded[i + 1..i + 3], 16).unwrap());
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn trashinfo_matches_the_freedesktop_format() {
+    let dir = scratch_dir("trashinfo");
+    let data_home = dir.join("data");
+    let file = dir.join("some file ü%.txt");
+    std::fs::write(&file, "hello").unwrap();
+
+    saferm(&data_home, &["--trash", file.to_str().unwrap()]);
+
+    assert!(!file.exists());
+    let trash = data_home.join("Trash");
+    let name = file.file_name().unwrap().to_str().unwrap();
+    assert_eq!(std::fs::read_to_string(trash.join("files").join(name)).unwrap(), "hello");
+
+    let info = std::fs::read_to_string(trash.join("info").join(format!("{}.trashinfo", name))).unwrap();
+    let mut lines = info.lines();
+    assert_eq!(lines.next(), Some("[Trash Info]"));
+
+    let path = lines.next().unwrap().strip_prefix("Path=").unwrap();
+    assert!(path.bytes().all(|b| b.is_ascii_graphic()), "Path should be percent-encoded: {}", path);
+    assert_eq!(url_decode(path), file.to_str().unwrap());
+
+    // YYYY-MM-DDThh:mm:ss
+    let date = lines.next().unwrap().strip_prefix("DeletionDate=").unwrap();
+    assert_eq!(date.len(), 19);
+    for (i, c) in date.chars().enumerate() {
+        match i {
+            4 | 7 => assert_eq!(c, '-'),
+            10 => assert_eq!(c, 'T'),
+            13 | 16 => assert_eq!(c, ':'),
+            _ => assert!(c.is_ascii_digit(), "bad DeletionDate {}", date),
+        }
+    }
+    assert_eq!(lines.next(), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn name_collisions_get_unique_trash_names() {
+    let dir = scratch_dir("collisions");
+    let data_home = dir.join("data");
+    let file = dir.join("file");
+
+    for contents in ["first", "second"] {
+        std::fs::write(&file, contents).unwrap();
+        saferm(&data_home, &["--trash", file.to_str().unwrap()]);
+    }
+
+    let trash = data_home.join("Trash");
+    let mut names: Vec<_> = std::fs::read_dir(trash.join("files")).unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    // every trashed file has a matching info file
+    for name in names {
+        assert!(trash.join("info").join(format!("{}.trashinfo", name)).exists());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
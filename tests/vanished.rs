@@ -0,0 +1,25 @@
+mod common;
+
+use saferm::dirfd::Dir;
+use saferm::{decide, Decision, DeleteOptions, Entry, SkipReason};
+use common::scratch_dir;
+
+#[test]
+fn entries_removed_during_the_walk_are_skipped() {
+    let start = scratch_dir("vanished");
+    let path = start.join("file");
+    std::fs::write(&path, "").unwrap();
+
+    // looked at, then removed by someone else before the checks get to it
+    let dir = Dir::open(&start).unwrap();
+    let stat = dir.stat_child("file".as_ref()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let entry = Entry { dir: &dir, name: "file".as_ref(), path: &path, stat: &stat, parent_dev: stat.dev(), depth: 1 };
+
+    let mut options = DeleteOptions::new(start.clone());
+    options.recursive = true;
+    assert!(matches!(decide(&entry, &options), Ok(Decision::Skip(SkipReason::Vanished))));
+    options.strict_canonicalize = true;
+    assert!(decide(&entry, &options).is_err());
+    std::fs::remove_dir_all(&start).unwrap();
+}